@@ -0,0 +1,61 @@
+//! Benchmarks of the json reader of the client, run with `cargo bench --bench json`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use es_deep_pager::deep_page_client::{json, EsJson};
+
+/// A search response of `hits` documents with `fields` fields each, like the batches of a deep search of wide documents.
+fn wide_response(hits: usize, fields: usize) -> String {
+    let hits = (0..hits).map(|hit| {
+        let source = (0..fields).map(|field| format!("\"field_{}\":\"value {} of doc {}\"", field, field, hit)).collect::<Vec<String>>().join(",");
+        format!("{{\"_index\":\"idx\",\"_id\":\"{}\",\"_score\":null,\"_source\":{{{}}},\"sort\":[{}]}}", hit, source, hit)
+    }).collect::<Vec<String>>().join(",");
+    format!("{{\"took\":1,\"timed_out\":false,\"hits\":{{\"total\":{{\"value\":1000,\"relation\":\"eq\"}},\"hits\":[{}]}}}}", hits)
+}
+
+/// Read 20 fields spread over the "_source" of each hit, the way a `search_map` closure reads a wide document.
+fn hit_fields(c: &mut Criterion) {
+    let response = json::parse(&wide_response(1000, 300)).unwrap();
+    let hits = match response.get("hits").and_then(|hits| hits.get("hits")) {
+        Some(EsJson::Array(hits)) => hits,
+        _ => unreachable!(),
+    };
+    let keys = (0..300).step_by(15).map(|field| format!("field_{}", field)).collect::<Vec<String>>();
+    let raw_keys = keys.iter().map(|key| format!("\"{}\"", key)).collect::<Vec<String>>();
+
+    let mut group = c.benchmark_group("hit_fields");
+    group.bench_function("key index", |b| b.iter(|| {
+        for hit in hits {
+            let source = hit.get("_source").unwrap();
+            for key in &keys {
+                black_box(source.get(key));
+            }
+        }
+    }));
+    // the linear scan over the keys that lookups did before objects had a key index.
+    group.bench_function("linear scan", |b| b.iter(|| {
+        for hit in hits {
+            let source = match hit.get("_source") {
+                Some(EsJson::Object(source)) => source,
+                _ => unreachable!(),
+            };
+            for key in &raw_keys {
+                black_box(source.iter().find(|(k, _)| k == key));
+            }
+        }
+    }));
+    group.finish();
+}
+
+/// Parse a 5 MB search response, the analyzer reads the bytes of the string in place without a copy of its characters.
+fn parse_response(c: &mut Criterion) {
+    let response = wide_response((5 << 20) / wide_response(1, 50).len(), 50);
+
+    let mut group = c.benchmark_group("parse");
+    group.sample_size(20);
+    group.throughput(criterion::Throughput::Bytes(response.len() as u64));
+    group.bench_function("5 MB response", |b| b.iter(|| json::parse(black_box(&response)).unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, hit_fields, parse_response);
+criterion_main!(benches);
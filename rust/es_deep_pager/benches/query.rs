@@ -0,0 +1,35 @@
+//! Benchmarks of the restricted queries of deep searches, run with `cargo bench --bench query --features bench`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use es_deep_pager::deep_page_client::bench;
+
+/// A large query, e.g. a bool query with a long terms filter, copied into the restricted query of every batch.
+fn large_query() -> String {
+    let terms = (0..5000).map(|i| format!("\"user-{}\"", i)).collect::<Vec<String>>().join(",");
+    format!("{{\"bool\":{{\"filter\":[{{\"terms\":{{\"user\":[{}]}}}},{{\"range\":{{\"age\":{{\"gte\":18}}}}}}]}}}}", terms)
+}
+
+/// The restricted queries of 500 batches, with the prefix of the query written once, 
+/// and with `format!` writing the whole query for each batch as the client did before.
+fn restricted_queries(c: &mut Criterion) {
+    let query = large_query();
+    let values = (0..500).map(|i| i * 1_000_003).collect::<Vec<i64>>();
+    let bounds = values.iter().map(|value| (*value, value + 999)).collect::<Vec<(i64, i64)>>();
+
+    let mut group = c.benchmark_group("restricted_queries");
+    group.bench_function("cmp prefix", |b| b.iter(|| bench::cmp_queries(black_box(&query), "id", &values)));
+    group.bench_function("cmp format", |b| b.iter(|| {
+        values.iter().map(|value| format!("{{\"bool\":{{\"must\":{},\"filter\":{{\"range\":{{\"{}\":{{\"gt\":{}}}}}}}}}}}", black_box(&query), "id", value)).collect::<Vec<String>>()
+    }));
+    group.bench_function("range prefix", |b| b.iter(|| bench::range_queries(black_box(&query), "id", &bounds)));
+    group.bench_function("range format", |b| b.iter(|| {
+        bounds.iter().map(|(start, end)| format!("{{\"bool\":{{\"must\":{},\"filter\":{{\"range\":{{\"{}\":{{\"gte\":{},\"lte\":{}}}}}}}}}}}", black_box(&query), "id", start, end)).collect::<Vec<String>>()
+    }));
+    group.finish();
+
+    // the prefix leaves the text unchanged.
+    assert_eq!(bench::cmp_queries(&query, "id", &values[..1])[0], format!("{{\"bool\":{{\"must\":{},\"filter\":{{\"range\":{{\"id\":{{\"gt\":0}}}}}}}}}}", query));
+}
+
+criterion_group!(benches, restricted_queries);
+criterion_main!(benches);
@@ -33,71 +33,907 @@
  //! https://www.elastic.co/guide/en/elasticsearch/reference/current/paginate-search-results.html
 pub mod deep_page_client{
 
-    use elasticsearch::http::{headers::HeaderMap, transport::Transport};
+    use std::fmt::Write;
+    use std::time::Duration;
+    use elasticsearch::auth::Credentials;
+    use elasticsearch::http::{headers::HeaderMap, transport::{Connection, ConnectionPool, Transport}, Method};
 
-    /// Error message.
-    pub enum Error { Message(String) }
+    /// Error of the client.
+    #[derive(Debug)]
+    pub enum Error {
+
+        /// The request could not be sent or its response could not be received. 
+        Transport(String),
+
+        /// Elasticsearch responded with a non-200 status, after any retries. 
+        Http { status: u16, body: String },
+
+        /// The response is not valid json or does not have the expected structure. 
+        /// The position is the byte offset of malformed json, if known. 
+        Parse { position: Option<usize>, detail: String },
+
+        /// A request was not completely received within the timeout setting of the client. 
+        Timeout(Duration),
+
+        /// A parameter or setting is invalid, no request was sent. 
+        InvalidParameter(String),
+
+        /// The point in time of the search has expired or was closed, the body is the error response of elasticsearch. 
+        /// A scan over it can not be continued, open a new point in time and restart the scan. 
+        PitExpired(String),
+
+        /// Any other error, e.g. failed shards or cancellation. 
+        Message(String),
+    }
+
+    impl Error {
+
+        fn invalid_json() -> Error {
+            Error::Parse { position: None, detail: String::from("invalid json") }
+        }
+
+        /// Prefix the message with the phase of the search that failed, e.g. "probe-min: ...". 
+        fn in_phase(self, phase: &str) -> Error {
+            match self {
+                Error::Transport(message) => Error::Transport(format!("{}: {}", phase, message)),
+                Error::Http { status, body } => Error::Http { status, body: format!("{}: {}", phase, body) },
+                Error::Parse { position, detail } => Error::Parse { position, detail: format!("{}: {}", phase, detail) },
+                Error::InvalidParameter(message) => Error::InvalidParameter(format!("{}: {}", phase, message)),
+                Error::Message(message) => Error::Message(format!("{}: {}", phase, message)),
+                error => error,
+            }
+        }
+    }
+
+    impl std::fmt::Display for Error {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Error::Transport(message) => write!(f, "transport error: {}", message),
+                Error::Http { status, body } => write!(f, "status {}: {}", status, body),
+                Error::Parse { position: Some(position), detail } => write!(f, "{} at position {}", detail, position),
+                Error::Parse { position: None, detail } => write!(f, "{}", detail),
+                Error::Timeout(timeout) => write!(f, "request timed out after {}s", timeout.as_secs_f64()),
+                Error::InvalidParameter(message) => write!(f, "{}", message),
+                Error::PitExpired(body) => write!(f, "point in time expired: {}", body),
+                Error::Message(message) => write!(f, "{}", message),
+            }
+        }
+    }
+
+    impl std::error::Error for Error {}
 
     /// Deep paging query client.
     /// 
-    /// # Parameters
-    ///
-    /// * `transport`: 
-    /// Elasticsearch official http transport. 
-    /// Reference: https://github.com/elastic/elasticsearch-rs
+    /// Use `Client::new` to create a client with default settings, or `Client::builder` to customize them.
+    /// 
+    /// Breaking change: the client used to be the tuple struct `Client(pub Transport)`, its fields are now private. 
+    /// Replace `Client(transport)` with `Client::new(transport)`, and `client.0` with `client.transport()`. 
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use es_deep_pager::deep_page_client;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let transport = elasticsearch::http::transport::Transport::single_node("http://localhost:9200")?;
+    /// let client = deep_page_client::Client::new(transport);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub struct Client {
+        transport: Transport,
+        nodes: usize,
+        max_from: i64,
+        batch_size: i64,
+        max_result_window: i64,
+        max_documents: Option<i64>,
+        cache_ttl: Option<Duration>,
+        max_response_bytes: Option<u64>,
+        max_json_depth: usize,
+        timeout: Option<Duration>,
+        retry: Option<Retry>,
+        server_error_retry: Option<Retry>,
+        allow_shard_failures: bool,
+        allow_timed_out: bool,
+        #[cfg(feature = "parallel")]
+        concurrency: usize,
+        #[cfg(feature = "gzip")]
+        compression: bool,
+        headers: HeaderMap,
+        cache: std::sync::Mutex<std::collections::HashMap<String, (Bounds<String>, std::time::Instant)>>,
+    }
+
+    const DEFAULT_MAX_FROM : i64 = 2000;
+    const DEFAULT_MAX_SIZE : i64 = 3000;
+    const DEFAULT_MAX_RESULT_WINDOW : i64 = 10000;
+
+    /// Response filter of `SearchOptions::filter_path`, every field of a search response the client reads. 
+    const SEARCH_FILTER_PATH : &str = "_shards,timed_out,pit_id,hits.total,hits.hits._id,hits.hits._index,hits.hits._score,hits.hits._source,hits.hits.sort,hits.hits.fields,hits.hits._seq_no,hits.hits._primary_term";
+
+    /// Keep alive time of the point in time opened by `Client::scan_all`, extended by each batch. 
+    const SCAN_KEEP_ALIVE : &str = "1m";
+
+    /// Field types whose sort values are numbers, which the deep paging cursor can do arithmetic on. 
+    const NUMERIC_TYPES : [&str; 11] = ["long", "integer", "short", "byte", "unsigned_long", "double", "float", "half_float", "scaled_float", "date", "date_nanos"];
+
+    /// Deep paging query client builder.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use es_deep_pager::deep_page_client;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let transport = elasticsearch::http::transport::Transport::single_node("http://localhost:9200")?;
+    /// let client = deep_page_client::Client::builder()
+    ///     .transport(transport)
+    ///     .max_from(5000)
+    ///     .max_size(5000)
+    ///     .build()?;
+    /// 
+    /// // the settings are validated by build, before any request is sent.
+    /// let invalid = deep_page_client::Client::builder().add_node("http://localhost:9200").max_size(100).batch_size(500).build();
+    /// assert!(matches!(invalid, Err(deep_page_client::Error::InvalidParameter(_))));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub struct ClientBuilder {
+        transport: Option<Transport>,
+        nodes: Vec<String>,
+        credentials: Option<Credentials>,
+        max_from: i64,
+        max_size: i64,
+        batch_size: Option<i64>,
+        max_result_window: i64,
+        max_documents: Option<i64>,
+        cache_ttl: Option<Duration>,
+        max_response_bytes: Option<u64>,
+        max_json_depth: usize,
+        timeout: Option<Duration>,
+        retry: Option<Retry>,
+        server_error_retry: Option<Retry>,
+        allow_shard_failures: bool,
+        allow_timed_out: bool,
+        #[cfg(feature = "parallel")]
+        concurrency: usize,
+        #[cfg(feature = "gzip")]
+        compression: bool,
+        headers: HeaderMap,
+    }
+
+    impl Default for ClientBuilder {
+        fn default() -> Self {
+            ClientBuilder {
+                transport: None,
+                nodes: vec![],
+                credentials: None,
+                max_from: DEFAULT_MAX_FROM,
+                max_size: DEFAULT_MAX_SIZE,
+                batch_size: None,
+                max_result_window: DEFAULT_MAX_RESULT_WINDOW,
+                max_documents: None,
+                cache_ttl: None,
+                max_response_bytes: None,
+                max_json_depth: DEFAULT_MAX_JSON_DEPTH,
+                timeout: None,
+                retry: None,
+                server_error_retry: None,
+                allow_shard_failures: false,
+                allow_timed_out: false,
+                #[cfg(feature = "parallel")]
+                concurrency: 1,
+                #[cfg(feature = "gzip")]
+                compression: false,
+                headers: HeaderMap::new(),
+            }
+        }
+    }
+
+    impl ClientBuilder {
+
+        /// Create a client builder with default settings.
+        pub fn new() -> ClientBuilder {
+            ClientBuilder::default()
+        }
+
+        /// Set the transport used to send requests.
+        /// 
+        /// # Parameters
+        /// 
+        /// * `transport`: 
+        ///   Elasticsearch official http transport. 
+        ///   Reference: https://github.com/elastic/elasticsearch-rs
+        pub fn transport(mut self, transport: Transport) -> ClientBuilder {
+            self.transport = Some(transport);
+            self
+        }
+
+        /// Add a node of the cluster, e.g. "http://node1:9200", instead of setting a transport. 
+        /// When nodes are added, the transport is built by `build`, and the requests are sent to the nodes in turn. 
+        /// A request failing with a transport error, e.g. when a node is down, is sent again to the next node, 
+        /// until every node is tried once. 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `url`: 
+        ///   The url of the node, including the scheme and the port. 
+        pub fn add_node(mut self, url: &str) -> ClientBuilder {
+            self.nodes.push(String::from(url));
+            self
+        }
+
+        /// Set the credentials of the transport built from the nodes added by `add_node`, e.g. `Credentials::Basic`. 
+        /// Can not be used with `transport`, `build` fails, set the credentials on that transport instead. 
+        /// Only one kind of credentials is sent, `auth`, `auth_api_key` and `auth_bearer` replace each other, the last call wins. 
+        pub fn auth(mut self, credentials: Credentials) -> ClientBuilder {
+            self.credentials = Some(credentials);
+            self
+        }
+
+        /// Authenticate with an API key, same as `auth` with `Credentials::ApiKey`. 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `id`, `key`: 
+        ///   The "id" and "api_key" returned by the create API key API of elasticsearch. 
+        ///   Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/security-api-create-api-key.html 
+        pub fn auth_api_key(self, id: &str, key: &str) -> ClientBuilder {
+            self.auth(Credentials::ApiKey(String::from(id), String::from(key)))
+        }
+
+        /// Authenticate with a bearer token, same as `auth` with `Credentials::Bearer`. 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `token`: 
+        ///   The access token, e.g. returned by the get token API of elasticsearch or a service account token. 
+        pub fn auth_bearer(self, token: &str) -> ClientBuilder {
+            self.auth(Credentials::Bearer(String::from(token)))
+        }
+
+        /// Set the largest "from" value sent in a single search request, defaults to 2000. 
+        /// When the "from" parameter of search exceeds this value, the client narrows it down with sort value range queries. 
+        /// It must be less than the max_result_window of the client.
+        pub fn max_from(mut self, max_from: i64) -> ClientBuilder {
+            self.max_from = max_from;
+            self
+        }
+
+        /// Set the largest "size" value sent in a single search request, defaults to 3000. 
+        /// Larger value means fewer round trips but larger response payloads. 
+        /// A batch starting at a large from value is shortened to stay within the max_result_window of the client.
+        pub fn max_size(mut self, max_size: i64) -> ClientBuilder {
+            self.max_size = max_size;
+            self
+        }
+
+        /// Set the number of documents fetched by each batch request of a search, defaults to max_size. 
+        /// Larger batches mean fewer round trips, smaller batches bound the memory of each response. 
+        /// Must not be greater than max_size. 
+        pub fn batch_size(mut self, batch_size: i64) -> ClientBuilder {
+            self.batch_size = Some(batch_size);
+            self
+        }
+
+        /// Set the max_result_window setting of the searched indices, defaults to 10000, the default of elasticsearch. 
+        /// No single search request asks for documents beyond this window, i.e. its "from" plus "size" never exceeds it, 
+        /// a batch that would cross the window is shortened and the rest is fetched by the following batches. 
+        /// Must be greater than max_from. 
+        pub fn max_result_window(mut self, max_result_window: i64) -> ClientBuilder {
+            self.max_result_window = max_result_window;
+            self
+        }
+
+        /// Set the largest number of documents a single search may return, defaults to unlimited. 
+        /// Searches with a larger `size` fail before sending any request, e.g. to guard a service exposing the client to 
+        /// untrusted callers against a mistyped size that would pull the entire index into memory. 
+        pub fn max_documents(mut self, max_documents: i64) -> ClientBuilder {
+            self.max_documents = Some(max_documents);
+            self
+        }
+
+        /// Set how long the bounds of a search are cached, defaults to no caching. 
+        /// A deep search first finds the total number of documents and the minimum and maximum sort values of its query, 
+        /// and the number of documents that have the sort field when it reverses the query for a page near the end, 
+        /// when the same index and query are paged again, e.g. from=0, 10000, 20000 in a UI, the cached bounds are reused 
+        /// and those requests are skipped. Only use it when the data set does not change within the ttl, 
+        /// or call `Client::invalidate` after writes. 
+        pub fn cache_ttl(mut self, cache_ttl: Duration) -> ClientBuilder {
+            self.cache_ttl = Some(cache_ttl);
+            self
+        }
+
+        /// Set the maximum size in bytes of each response body, defaults to no limit. 
+        /// A response whose "Content-Length" exceeds it is rejected before its body is read, so a single huge document 
+        /// can not exhaust the memory of a long running job. Responses without a length, e.g. chunked or compressed ones, 
+        /// are checked once they are read. Either way the request fails with "response exceeds max_response_bytes". 
+        pub fn max_response_bytes(mut self, max_response_bytes: u64) -> ClientBuilder {
+            self.max_response_bytes = Some(max_response_bytes);
+            self
+        }
+
+        /// Set the maximum nesting depth of the arrays and objects of each response, defaults to 256. 
+        /// Deeper responses, e.g. documents holding deeply nested config blobs, fail with `Error::Parse` "json nesting too deep", 
+        /// raise it to read them. The parser keeps its own stack, so the limit only bounds the work on hostile input. 
+        pub fn max_json_depth(mut self, max_json_depth: usize) -> ClientBuilder {
+            self.max_json_depth = max_json_depth;
+            self
+        }
+
+        /// Set the timeout of each request sent to elasticsearch, defaults to no timeout. 
+        /// A search sends many requests (count, probes, binary search, batches), each of them is limited independently. 
+        pub fn timeout(mut self, timeout: Duration) -> ClientBuilder {
+            self.timeout = Some(timeout);
+            self
+        }
+
+        /// Set the retry settings of throttled requests, defaults to no retry. 
+        /// Requests that fail with 429 Too Many Requests (and optionally 503 Service Unavailable) are retried, 
+        /// other failed requests fail immediately. 
+        pub fn retry(mut self, retry: Retry) -> ClientBuilder {
+            self.retry = Some(retry);
+            self
+        }
+
+        /// Set the retry settings of transient server errors, defaults to no retry. 
+        /// Requests that fail with 502 Bad Gateway, 503 Service Unavailable or 504 Gateway Timeout, e.g. during a rolling 
+        /// restart of the cluster, are retried with their own retry count and backoff, independent of the throttling retries. 
+        /// 503 responses are retried by the throttling retry settings instead when their `retry_unavailable` is true. 
+        /// Other server errors and client errors fail immediately. The `retry_unavailable` of these settings is not used. 
+        pub fn server_error_retry(mut self, retry: Retry) -> ClientBuilder {
+            self.server_error_retry = Some(retry);
+            self
+        }
+
+        /// Set whether search responses with failed shards are accepted, defaults to false. 
+        /// When some shards fail, elasticsearch still returns the hits of the successful shards, so the result may be incomplete. 
+        /// By default such responses fail with an error listing the shard failure reasons, 
+        /// set this to true to accept best-effort results. 
+        pub fn allow_shard_failures(mut self, allow_shard_failures: bool) -> ClientBuilder {
+            self.allow_shard_failures = allow_shard_failures;
+            self
+        }
+
+        /// Set whether timed out search responses are accepted, defaults to false. 
+        /// When the search timeout of elasticsearch elapses, it still returns the hits gathered so far with "timed_out": true, 
+        /// so the result may be incomplete. By default such responses fail with an error, 
+        /// set this to true to accept best-effort results. 
+        pub fn allow_timed_out(mut self, allow_timed_out: bool) -> ClientBuilder {
+            self.allow_timed_out = allow_timed_out;
+            self
+        }
+
+        /// Set the default headers of each request sent to elasticsearch, e.g. an "X-Opaque-Id" for request tracing. 
+        /// Headers specified in `SearchOptions::headers` replace the default values of the same names. 
+        pub fn headers(mut self, headers: HeaderMap) -> ClientBuilder {
+            self.headers = headers;
+            self
+        }
+
+        /// Set the number of concurrent segments of a search, defaults to 1. Requires the `parallel` feature. 
+        /// When the `size` of a search exceeds the batch size, the requested documents are split into consecutive segments, 
+        /// each segment is fetched by its own sequence of requests, and up to this number of segments run concurrently. 
+        /// Each segment locates its own starting sort value, so this trades some extra count requests for wall-clock time. 
+        #[cfg(feature = "parallel")]
+        pub fn concurrency(mut self, concurrency: usize) -> ClientBuilder {
+            self.concurrency = concurrency;
+            self
+        }
+
+        /// Set whether request and response bodies are compressed with gzip, defaults to false. Requires the `gzip` feature. 
+        /// When enabled, request bodies are sent with "Content-Encoding: gzip", and "Accept-Encoding: gzip" is requested, 
+        /// gzip responses are decompressed before they are analyzed. 
+        /// The elasticsearch cluster must enable "http.compression" to compress its responses. 
+        #[cfg(feature = "gzip")]
+        pub fn compression(mut self, compression: bool) -> ClientBuilder {
+            self.compression = compression;
+            self
+        }
+
+        /// Validate settings and create the client.
+        pub fn build(mut self) -> Result<Client, Error> {
+            let transport = match self.transport.take() {
+                Some(_) if !self.nodes.is_empty() => return Err(Error::InvalidParameter(String::from("transport can not be used with add_node."))),
+                Some(_) if self.credentials.is_some() => return Err(Error::InvalidParameter(String::from("transport can not be used with auth, set the credentials on the transport."))),
+                Some(transport) => transport,
+                None if !self.nodes.is_empty() => self.build_transport()?,
+                None => return Err(Error::InvalidParameter(String::from("transport can not be empty."))),
+            };
+            if self.max_from < 1 {
+                return Err(Error::InvalidParameter(String::from("max_from must be positive.")));
+            }
+            if self.max_size < 1 {
+                return Err(Error::InvalidParameter(String::from("max_size must be positive.")));
+            }
+            if self.batch_size.is_some_and(|batch_size| batch_size < 1 || batch_size > self.max_size) {
+                return Err(Error::InvalidParameter(String::from("batch_size must be positive and not greater than max_size.")));
+            }
+            if self.max_documents.is_some_and(|max_documents| max_documents < 0) {
+                return Err(Error::InvalidParameter(String::from("max_documents can not be negative.")));
+            }
+            if self.max_result_window <= self.max_from {
+                return Err(Error::InvalidParameter(String::from("max_result_window must be greater than max_from.")));
+            }
+            #[cfg(feature = "parallel")]
+            if self.concurrency < 1 {
+                return Err(Error::InvalidParameter(String::from("concurrency must be positive.")));
+            }
+            Ok(self.create(transport))
+        }
+
+        /// Build a transport sending requests to the added nodes in turn. 
+        fn build_transport(&mut self) -> Result<Transport, Error> {
+            let mut connections = vec![];
+            for node in &self.nodes {
+                let url = elasticsearch::http::Url::parse(node).map_err(|e| Error::InvalidParameter(format!("node {} is not a valid url: {}", node, e)))?;
+                connections.push(Connection::new(url));
+            }
+            let pool = RoundRobinPool { connections, next: Default::default() };
+            let mut builder = elasticsearch::http::transport::TransportBuilder::new(pool);
+            if let Some(credentials) = self.credentials.take() {
+                builder = builder.auth(credentials);
+            }
+            builder.build().map_err(|e| Error::Transport(format!("{}", e)))
+        }
+
+        /// Create the client with the settings of this builder.
+        fn create(self, transport: Transport) -> Client {
+            Client {
+                transport,
+                nodes: self.nodes.len().max(1),
+                max_from: self.max_from,
+                batch_size: self.batch_size.unwrap_or(self.max_size),
+                max_result_window: self.max_result_window,
+                max_documents: self.max_documents,
+                cache_ttl: self.cache_ttl,
+                max_response_bytes: self.max_response_bytes,
+                max_json_depth: self.max_json_depth,
+                timeout: self.timeout,
+                retry: self.retry,
+                server_error_retry: self.server_error_retry,
+                allow_shard_failures: self.allow_shard_failures,
+                allow_timed_out: self.allow_timed_out,
+                #[cfg(feature = "parallel")]
+                concurrency: self.concurrency,
+                #[cfg(feature = "gzip")]
+                compression: self.compression,
+                headers: self.headers,
+                cache: Default::default(),
+            }
+        }
+    }
+
+    /// Connection pool of the nodes added by `ClientBuilder::add_node`, each request takes the next node in turn. 
+    #[derive(Debug, Clone)]
+    struct RoundRobinPool {
+        connections: Vec<Connection>,
+        next: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl ConnectionPool for RoundRobinPool {
+        fn next(&self) -> &Connection {
+            let index = self.next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            &self.connections[index % self.connections.len()]
+        }
+    }
+
+    /// Optional parameters of a search.
     /// 
     /// # Examples
     /// 
     /// ```
-    /// let client = deep_page_client::Client(transport);
+    /// # use es_deep_pager::deep_page_client;
+    /// let options = deep_page_client::SearchOptions { tie_break: Some("id"), ..Default::default() };
+    /// ```
+    #[derive(Default)]
+    pub struct SearchOptions<'a> {
+
+        /// Secondary sort field used as a tie-breaker, e.g. "id". 
+        /// When specified, the sort field does not need to be strictly unique, but the combination of the sort field and 
+        /// the tie-breaker field must be unique. Both fields are placed in the "sort" field of the request body in the same order. 
+        /// At batch boundaries, the next batch continues after the (sort, tie-breaker) values of the last document, 
+        /// so documents sharing the boundary sort value are neither skipped nor repeated. 
+        /// The deep paging from calculation still counts documents by the sort field only, so the tie-breaker only 
+        /// affects documents that share a sort value. 
+        /// Without it, when too many documents share the sort values at a large from to skip them with from, they are skipped 
+        /// with "search_after" over a point in time instead, the `pit` of the options or one opened on the index and closed 
+        /// when the search finishes. 
+        /// A point in time opened here is opened after the count and the probes of the sort values ran against the live index, 
+        /// so the skip is not consistent with them when documents are written meanwhile, and the window may be shifted by the 
+        /// documents written. Specify `pit` to run the whole search against one snapshot. 
+        pub tie_break: Option<&'a str>,
+
+        /// Deduplicate documents at batch boundaries by "_id" when the sort field is not strictly unique. 
+        /// At batch boundaries, the next batch continues from the sort value of the last document inclusively ("gte" or "lte"), 
+        /// and excludes the ids of the documents already returned with that sort value. 
+        /// The excluded ids grow with the number of documents sharing a boundary sort value, so this is meant for fields that 
+        /// are mostly unique. Ignored when `tie_break` is specified. 
+        pub dedup: bool,
+
+        /// Skip the documents that do not have the sort field. 
+        /// When specified, an "exists" filter on the sort field is added to the query, so the documents missing the sort field 
+        /// are excluded from the results and from the counts of the deep paging calculation. 
+        /// When not specified, the deep paging calculation assumes that every document has the sort field, 
+        /// and an error naming the "_id" of the document is returned when a document missing the sort field is encountered. 
+        pub skip_missing_sort: bool,
+
+        /// Where the documents missing the sort field are placed in the batches, "_first", "_last" or a literal value, e.g. "0". 
+        /// When specified, the sort clause of the batches is written in the long form, e.g. {"id":{"order":"asc","missing":"_last"}}, 
+        /// otherwise the short form {"id":"asc"} is used and elasticsearch places them last. 
+        /// The count and range filters of the deep paging calculation only match documents that have the sort field, 
+        /// so the documents missing it are excluded from them and are handled at the ends of the data set: 
+        /// deep from values and the batches after the first one only cover the documents with a sort value, 
+        /// and the reverse query direction optimization is not used. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/sort-search-results.html#_missing_values 
+        pub sort_missing: Option<&'a str>,
+
+        /// How the total number of documents that meet query conditions is fetched for deep pages. 
+        /// The total is used to decide whether to reverse the query direction for documents near the end of the data set. 
+        pub total_hits: TotalHits,
+
+        /// Field to collapse the results on, e.g. "user_id", only the top document of each value is returned. 
+        /// This will be placed in the "collapse" field of the request body. 
+        /// Collapsing changes the meaning of from and size to groups, which the deep paging calculation and the batch 
+        /// boundaries can not follow, so the search must be fetched in a single request: from can not be greater than 
+        /// `max_from`, and size can not be greater than `batch_size`. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/collapse-search-results.html 
+        pub collapse: Option<&'a str>,
+
+        /// Minimum score of the returned documents, documents with a lower score are dropped. 
+        /// This will be placed in the "min_score" field of the request body. 
+        /// The counts of the deep paging calculation do not apply the minimum score, so from can not be greater than `max_from`, 
+        /// larger searches continue batch by batch after the sort value of the last document. 
+        pub min_score: Option<f64>,
+
+        /// Filter applied to the hits after the query, e.g. {"term":{"color":"red"}}. 
+        /// This will be placed in the "post_filter" field of the request body. 
+        /// The count requests of the deep paging calculation do not support it, so they add it to the query as a filter instead, 
+        /// from, size and the total all refer to the post filtered hits. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/filter-search-results.html#post-filter 
+        pub post_filter: Option<&'a str>,
+
+        /// Maximum number of documents collected by each shard, e.g. 100000, to cap the work of exploratory searches. 
+        /// This will be placed in the "terminate_after" field of the search request bodies. 
+        /// Shards stop collecting early, so the results are partial: the probes and batches only see the collected documents, 
+        /// the reverse direction optimization near the end of the data set is disabled, and fewer than `size` documents may be returned. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-search.html#search-terminate-after 
+        pub terminate_after: Option<i64>,
+
+        /// Check that the sort field has the same type in all the indices matched by the index name before the search, 
+        /// e.g. for "logs-2023-*,logs-2024-*" whose mappings differ. 
+        /// When specified, a "_field_caps" request is sent first, and an error naming the conflicting types and indices 
+        /// is returned instead of searching. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-field-caps.html 
+        pub check_sort_type: bool,
+
+        /// Check that the index exists and the sort field is mapped with a numeric or date type before the search. 
+        /// When specified, a "_field_caps" request is sent first, and a precise error like "index not found" or 
+        /// "sort field 'x' is not numeric" is returned before any paging work, instead of a failure in the middle of the search. 
+        /// This costs one extra request per search. 
+        pub preflight: bool,
+
+        /// Source fields to exclude, e.g. ["big_text", "obj1.*"]. 
+        /// When specified, the "_source" field of the request body becomes {"includes":[...],"excludes":[...]}, 
+        /// with the includes taken from the `source` parameter of the search. 
+        /// The sort field may be excluded, the deep paging cursor reads sort values from the "sort" field of each hit 
+        /// and the internal probe requests use their own source filter. 
+        pub source_excludes: Option<&'a Vec<&'a str>>,
+
+        /// Source fields of each batch, called with the index of the batch starting from 0, e.g. to interleave light and 
+        /// heavy projections of wide documents. When specified, the returned fields, static strings like vec!["id", "title"], 
+        /// replace the `source` parameter of the search for that batch, the probe and count requests keep their own projections. 
+        /// The fields are checked like `source`, a field containing a quote or a backslash fails the search. 
+        /// With `ClientBuilder::concurrency`, each concurrent segment counts its batches from 0. 
+        pub source_resolver: Option<&'a (dyn Fn(usize) -> Vec<&'static str> + Sync)>,
+
+        /// Fields returned from doc values, e.g. ["id", "price", "created_at"]. 
+        /// This will be placed in the "docvalue_fields" field of the request body, and the values of each document are returned 
+        /// in its "fields" object as arrays, e.g. {"price":[12.5]}. When neither `source` nor `source_excludes` is specified, 
+        /// "_source" is disabled, so wide documents are returned as compact doc value projections. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-fields.html#docvalue-fields 
+        pub docvalue_fields: Option<&'a Vec<&'a str>>,
+
+        /// Fields returned formatted by their mapping, e.g. ["created_at", "location", "my_runtime_field"]. 
+        /// This will be placed in the "fields" field of the request body. Unlike "_source", the values respect the mapping, 
+        /// e.g. date formats and runtime fields, and are returned in the "fields" object of each document as arrays. 
+        /// The "_source" is still controlled by the `source` parameter and `source_excludes`, when neither is specified, 
+        /// "_source" is disabled and the documents only carry their "fields", read into `Hit::fields` by `Client::search_hits`. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-fields.html#search-fields-param 
+        pub fields: Option<&'a Vec<&'a str>>,
+
+        /// Return the sequence number and primary term of each document, for conditional updates with "if_seq_no" and 
+        /// "if_primary_term". This will be placed in the "seq_no_primary_term" field of the request body, 
+        /// and the values are read into `Hit::seq_no` and `Hit::primary_term` by `Client::search_hits`. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/optimistic-concurrency-control.html 
+        pub seq_no_primary_term: bool,
+
+        /// Point in time id returned by `Client::open_pit`. 
+        /// When specified, all the requests of the search are executed against the frozen view of the point in time, 
+        /// so documents written during the search do not shift between batches. This will be placed in the "pit" field 
+        /// of the request body, and the requests target "_search" without the index in the path. 
+        /// Elasticsearch may return a new id with each response, each batch uses the id returned by the previous one. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/point-in-time-api.html 
+        pub pit: Option<&'a str>,
+
+        /// Keep alive time of the point in time, e.g. "1m", extended by each request of the search. 
+        /// If not specified, the keep alive time is not extended. 
+        pub pit_keep_alive: Option<&'a str>,
+
+        /// Routing value of the search, e.g. "user1". 
+        /// When documents are indexed with custom routing, searching within a single routing value only hits the shards 
+        /// of that value. This will be placed in the "routing" parameter of the search and count request urls. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/mapping-routing-field.html 
+        pub routing: Option<&'a str>,
+
+        /// Preference of the search, e.g. "_local" or a custom session string. 
+        /// Pins the requests of the search to the same shard copies, so that the order of documents stays consistent 
+        /// between batches. This will be placed in the "preference" parameter of the search and count request urls. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-search.html#search-preference 
+        pub preference: Option<&'a str>,
+
+        /// Use the shard request cache for the requests of the search, e.g. when the same filter is paged again and again. 
+        /// When specified, "request_cache=true" is placed in the search request urls. The countAPI does not support it, 
+        /// so the counts of the binary search are read from searches with size 0 instead, which the cache then serves. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/shard-request-cache.html 
+        pub request_cache: bool,
+
+        /// Filter the search responses down to the fields the client reads, to shrink the payloads of large scans. 
+        /// When specified, the "filter_path" parameter is placed in the search request urls, keeping "_shards", "timed_out", 
+        /// "pit_id", "hits.total" and the "_id", "_index", "_score", "_source", "sort", "fields", "_seq_no" and "_primary_term" 
+        /// of each hit, other fields of the hits, e.g. "_routing" or "highlight", are not returned. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/common-options.html#common-options-response-filtering 
+        pub filter_path: bool,
+
+        /// Mapping type of the documents on legacy 6.x clusters, e.g. "_doc" or "my-type". 
+        /// When specified, the search and count requests target "{index}/{doc_type}/_search" and "{index}/{doc_type}/_count". 
+        /// The "include_type_name" parameter of 6.x and 7.x only applies to the index and mapping APIs, which the search does not call. 
+        /// Mapping types are removed since elasticsearch 8.0, leave this empty there. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/7.17/removal-of-types.html 
+        pub doc_type: Option<&'a str>,
+
+        /// Headers of each request of the search, merged into the default headers of the client. 
+        /// Headers specified here replace the default values of the same names. 
+        pub headers: Option<&'a HeaderMap>,
+
+        /// Deadline of the whole search, e.g. `Instant::now() + Duration::from_secs(10)`. 
+        /// The client timeout limits each request, while a deep search may send hundreds of requests, 
+        /// the deadline is checked before each of them, once it has passed, the search fails with "deadline exceeded". 
+        pub deadline: Option<std::time::Instant>,
+
+        /// Return the documents gathered so far instead of failing when the deadline passes between batches. 
+        /// The result then contains the first documents of the requested ones, fewer than requested. 
+        /// A partial result is still `Ok`, set `stats` and check `SearchStats::truncated` to tell it from a complete one. 
+        /// Searches with this set are not split into concurrent segments, and the reverse query direction optimization is not used. 
+        pub partial_on_deadline: bool,
+
+        /// Cancellation token of the search. Requires the `cancellation` feature. 
+        /// The token is checked before each request of the search (count, probes, binary search, batches), 
+        /// once it is cancelled, the search returns an error instead of sending more requests. 
+        #[cfg(feature = "cancellation")]
+        pub cancellation: Option<&'a tokio_util::sync::CancellationToken>,
+
+        /// Statistics accumulating the requests, documents and bytes of the search, see `SearchStats`. 
+        /// The same statistics can be shared by several searches to measure them together, or use `Client::search_with_stats`. 
+        pub stats: Option<&'a SearchStats>,
+    }
+
+    impl SearchOptions<'_> {
+
+        /// Append the url parameters of the options to the request path. 
+        fn build_url(&self, path: String) -> String {
+            let mut params = vec![];
+            if let Some(routing) = self.routing {
+                params.push(format!("routing={}", encode_url_component(routing)));
+            }
+            if let Some(preference) = self.preference {
+                params.push(format!("preference={}", encode_url_component(preference)));
+            }
+            if self.request_cache {
+                params.push(String::from("request_cache=true"));
+            }
+            if params.is_empty() {
+                path
+            } else {
+                format!("{}?{}", path, params.join("&"))
+            }
+        }
+
+        /// Build the path of an endpoint of the index, with the mapping type when specified, e.g. "my-index/my-type/_search". 
+        fn index_path(&self, index: &str, endpoint: &str) -> String {
+            match self.doc_type {
+                Some(doc_type) => format!("{}/{}/{}", index, encode_url_component(doc_type), endpoint),
+                None => format!("{}/{}", index, endpoint),
+            }
+        }
+
+        /// Return an error if the search is cancelled or its deadline has passed. 
+        fn check_cancelled(&self) -> Result<(), Error> {
+            if self.is_expired() {
+                return Err(Error::Message(String::from("deadline exceeded")));
+            }
+            #[cfg(feature = "cancellation")]
+            if self.cancellation.is_some_and(|token| token.is_cancelled()) {
+                return Err(Error::Message(String::from("cancelled")));
+            }
+            Ok(())
+        }
+
+        /// Add a request and the sizes of its bodies to the statistics of the search, if specified. 
+        fn record_request(&self, request_bytes: usize, response_bytes: usize) {
+            if let Some(stats) = self.stats {
+                stats.requests.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                stats.bytes.fetch_add((request_bytes + response_bytes) as u64, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+
+        fn is_expired(&self) -> bool {
+            self.deadline.is_some_and(|deadline| std::time::Instant::now() >= deadline)
+        }
+    }
+
+    /// Parameters of a search, built by chained setters. 
+    /// The parameters are the same as the arguments of `Client::search_with_options`. 
+    /// 
+    /// # Examples
+    /// 
+    /// ```no_run
+    /// # use es_deep_pager::deep_page_client;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = deep_page_client::doc_client();
+    /// let source = vec!["id", "name"];
+    /// let request = deep_page_client::SearchRequest::new("test_data_*")
+    ///     .query("{\"match_all\":{}}")
+    ///     .source(&source)
+    ///     .sort("id")
+    ///     .ascending(true)
+    ///     .from(100000000)
+    ///     .size(10000);
+    /// let result = client.search_request(&request).await;
+    /// # Ok(())
+    /// # }
     /// ```
-    pub struct Client(pub Transport);
+    pub struct SearchRequest<'a> {
+        index: &'a str,
+        query: &'a str,
+        source: Option<&'a Vec<&'a str>>,
+        sort: &'a str,
+        asc: bool,
+        from: i64,
+        size: i64,
+        options: SearchOptions<'a>,
+    }
+
+    impl<'a> SearchRequest<'a> {
+
+        /// Create a search request of the index, all documents sorted ascending, from 0 and size 10. 
+        pub fn new(index: &'a str) -> SearchRequest<'a> {
+            SearchRequest {
+                index,
+                query: "",
+                source: None,
+                sort: "",
+                asc: true,
+                from: 0,
+                size: 10,
+                options: SearchOptions::default(),
+            }
+        }
+
+        /// Set the query Dsl, same as the `query` argument of `Client::search`. 
+        pub fn query(mut self, query: &'a str) -> SearchRequest<'a> {
+            self.query = query;
+            self
+        }
+
+        /// Set the source filter, same as the `source` argument of `Client::search`. 
+        pub fn source(mut self, source: &'a Vec<&'a str>) -> SearchRequest<'a> {
+            self.source = Some(source);
+            self
+        }
+
+        /// Set the unique number sort field, same as the `sort` argument of `Client::search`. 
+        pub fn sort(mut self, sort: &'a str) -> SearchRequest<'a> {
+            self.sort = sort;
+            self
+        }
+
+        /// Set the sort order, same as the `asc` argument of `Client::search`. 
+        pub fn ascending(mut self, asc: bool) -> SearchRequest<'a> {
+            self.asc = asc;
+            self
+        }
+
+        /// Set the starting document offset, same as the `from` argument of `Client::search`. 
+        pub fn from(mut self, from: i64) -> SearchRequest<'a> {
+            self.from = from;
+            self
+        }
+
+        /// Set the number of hits to return, same as the `size` argument of `Client::search`. 
+        pub fn size(mut self, size: i64) -> SearchRequest<'a> {
+            self.size = size;
+            self
+        }
 
-    const MAX_FROM : i64 = 2000;
-    const MAX_SIZE : i64 = 3000;
+        /// Set the optional parameters, see `SearchOptions`. 
+        pub fn options(mut self, options: SearchOptions<'a>) -> SearchRequest<'a> {
+            self.options = options;
+            self
+        }
+    }
 
     impl Client {
 
+        /// Create a client with default settings.
+        /// 
+        /// # Parameters
+        /// 
+        /// * `transport`: 
+        ///   Elasticsearch official http transport. 
+        ///   Reference: https://github.com/elastic/elasticsearch-rs
+        pub fn new(transport: Transport) -> Client {
+            ClientBuilder::new().create(transport)
+        }
+
+        /// Create a client builder to customize settings.
+        pub fn builder() -> ClientBuilder {
+            ClientBuilder::new()
+        }
+
+        /// Get the elasticsearch transport the client sends its requests with, e.g. to call other APIs of the cluster. 
+        pub fn transport(&self) -> &Transport {
+            &self.transport
+        }
+
+        /// Clear the cached bounds of searches, see `ClientBuilder::cache_ttl`. 
+        pub fn invalidate(&self) {
+            self.cache.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        }
+
         /// Search method, receives parameters such as index, queryDsl, from, size, etc., and call the searchAPI of elasticsearch to query data. 
         /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-your-data.html 
         /// 
         /// # Parameters
         /// 
         /// * `index`: 
-        /// The index name for query, can use wildcards, e.g. my-index-*. This will be placed in search request path. 
-        /// For lower versions of elasticsearch, it can contains type, e.g. my-index/my-type. 
-        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-search.html 
+        ///   The index name for query, can use wildcards, e.g. my-index-*. This will be placed in search request path. 
+        ///   For lower versions of elasticsearch, it can contains type, e.g. my-index/my-type. 
+        ///   Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-search.html 
         /// 
         /// * `query`: 
-        /// Query Dsl for query, this is a json formatted string, e.g. {"match_all":{}}. 
-        /// This will be placed in the "query" field of the request body. 
-        /// If not specified, the search will return all documents in the index. 
-        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/query-dsl.html 
+        ///   Query Dsl for query, this is a json formatted string, e.g. {"match_all":{}}. 
+        ///   This will be placed in the "query" field of the request body. 
+        ///   If not specified, the search will return all documents in the index. 
+        ///   Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/query-dsl.html 
         /// 
         /// * `source`: 
-        /// source filter for query, e.g. ["column1", "column2", "obj1.*", "obj2.*" ]. 
-        /// This will be placed in the "_source" field of the request body. 
-        /// If not specified, The query will return fields based on the default settings of the index. 
-        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-fields.html
+        ///   source filter for query, e.g. ["column1", "column2", "obj1.*", "obj2.*" ]. 
+        ///   This will be placed in the "_source" field of the request body. 
+        ///   If not specified, The query will return fields based on the default settings of the index. 
+        ///   Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-fields.html
         /// 
         /// * `sort`: 
-        /// The sort field for query, e.g. "id". This will be placed in the "sort" field of the request body. 
-        /// In order to implement fast large from parameter query, the queried data must be a well-ordered set. 
-        /// All the documents to be queried must have at least one unique number field, which is a numeric type and stores the 
-        /// unique number of each document. The available range of the number is the entire long integer, which can be negative 
-        /// and discontinuous, but the number of each document must not be repeated. 
-        /// When performing fast from query, a unique number field must be used as sorting. 
+        ///   The sort field for query, e.g. "id". This will be placed in the "sort" field of the request body. 
+        ///   In order to implement fast large from parameter query, the queried data must be a well-ordered set. 
+        ///   All the documents to be queried must have at least one unique number field, which is a numeric type and stores the 
+        ///   unique number of each document. The available range of the number is the entire long integer, which can be negative 
+        ///   and discontinuous, but the number of each document must not be repeated. 
+        ///   When performing fast from query, a unique number field must be used as sorting. 
+        ///   If some documents share a sort value anyway, they are ordered by "_id" in both query directions, like a query 
+        ///   sorted by the field and then by "_id", which can not be sorted on by default since elasticsearch 8.0. 
+        ///   The run of tied documents at each end of the window is read whole when the window cuts it, its ids are fetched 
+        ///   to pick the documents of the window, which costs more requests for large runs. 
+        ///   The other searches keep the order elasticsearch returns tied documents in. Set `SearchOptions::tie_break` for an 
+        ///   order by another unique field. 
         /// 
         /// * `asc`: 
-        /// Sort order of the unique number field, if true, means ascending, if false, means descending.  
+        ///   Sort order of the unique number field, if true, means ascending, if false, means descending.  
         /// 
         /// * `from`: 
-        /// Starting document offset, how many documents to skip. a non-negative number. e.g. 100000000 
-        /// Using this client, you can use very large from parameter without changing the default max_result_window setting of the index. 
+        ///   Starting document offset, how many documents to skip. a non-negative number. e.g. 100000000 
+        ///   Using this client, you can use very large from parameter without changing the default max_result_window setting of the index. 
         /// 
         /// * `size`: 
-        /// The number of hits to return. a non-negative number. e.g. 1000000 
-        /// Using this client, you can use large value parameter without changing the default max_result_window setting of the index. 
+        ///   The number of hits to return. a non-negative number. e.g. 1000000 
+        ///   Using this client, you can use large value parameter without changing the default max_result_window setting of the index. 
         /// 
         /// # Return
         /// A list of all documents that match the query. Each document is a json formatted string, you can choose your favorite json deserializer to parse it. 
@@ -105,7 +941,10 @@ pub mod deep_page_client{
         /// 
         /// # Examples
         /// 
-        /// ```
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
         /// let result = client.search(
         ///     "test_data_*", 
         ///     "{\"match_all\":{}}",
@@ -114,455 +953,5450 @@ pub mod deep_page_client{
         ///     true, 
         ///     100000000, 
         ///     10000).await;
+        /// # Ok(())
+        /// # }
         /// ```
+        #[allow(clippy::too_many_arguments)]
         pub async fn search(&self, index: &str, query: &str, source: Option<&Vec<&str>>, sort: &str, asc: bool, from: i64, size: i64) -> Result<Vec<String>, Error> {
-            
-            // validate parameters
-            if index.is_empty() {
-                return Err(Error::Message(String::from("index can not be empty.")));
+            let mut request = SearchRequest::new(index).query(query).sort(sort).ascending(asc).from(from).size(size);
+            if let Some(source) = source {
+                request = request.source(source);
+            }
+            self.search_request(&request).await
+        }
+
+        /// Search method with a `SearchRequest`, same as `search_with_options`, but the parameters are named by the builder 
+        /// instead of positional arguments. 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `request`: 
+        ///   The parameters of the search, see `SearchRequest`. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let request = deep_page_client::SearchRequest::new("test_data_*").sort("id").ascending(true).from(100000000).size(10000);
+        /// let result = client.search_request(&request).await;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn search_request(&self, request: &SearchRequest<'_>) -> Result<Vec<String>, Error> {
+            self.search_with_options(request.index, request.query, request.source, request.sort, request.asc, request.from, request.size, &request.options).await
+        }
+
+        /// Search method with optional parameters, same as `search`, but accepts `SearchOptions` to customize the query. 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `index`, `query`, `source`, `sort`, `asc`, `from`, `size`: 
+        ///   Same as `search`. 
+        /// 
+        /// * `options`: 
+        ///   Optional parameters of the query, see `SearchOptions`. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let options = deep_page_client::SearchOptions { tie_break: Some("id"), ..Default::default() };
+        /// let result = client.search_with_options("test_data_*", "", None, "timestamp", true, 100000000, 10000, &options).await;
+        /// # Ok(())
+        /// # }
+        /// ```
+        #[allow(clippy::too_many_arguments)]
+        pub async fn search_with_options(&self, index: &str, query: &str, source: Option<&Vec<&str>>, sort: &str, asc: bool, from: i64, size: i64, options: &SearchOptions<'_>) -> Result<Vec<String>, Error> {
+            self.search_map::<i64, _, _>(index, query, source, sort, asc, from, size, options, |hit| Ok(EsJsonAnalyzer::to_json(hit))).await
+        }
+
+        /// Search method returning statistics, same as `search_with_options`, but also returns the number of requests sent, 
+        /// documents fetched and bytes transferred by the search, e.g. to correlate page views with the load of the cluster. 
+        /// A deep search sends a count, two probes, the binary search counts and the batches, the statistics show this amplification. 
+        /// A deep page near the end of the data set also counts the documents that have the sort field before reversing the query. 
+        /// 
+        /// # Parameters
+        /// 
+        /// Same as `search_with_options`, the `stats` of the options are replaced by the returned ones. 
+        /// 
+        /// # Return
+        /// A list of the documents, same as `search`, and the statistics of the search. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let (result, stats) = client.search_with_stats("test_data_*", "", None, "id", true, 100000000, 10000, &Default::default()).await?;
+        /// println!("{} requests, {} bytes", stats.requests(), stats.bytes());
+        /// # Ok(())
+        /// # }
+        /// ```
+        #[allow(clippy::too_many_arguments)]
+        pub async fn search_with_stats(&self, index: &str, query: &str, source: Option<&Vec<&str>>, sort: &str, asc: bool, from: i64, size: i64, options: &SearchOptions<'_>) -> Result<(Vec<String>, SearchStats), Error> {
+            let stats = SearchStats::default();
+            let options = SearchOptions { stats: Some(&stats), ..*options };
+            let list = self.search_with_options(index, query, source, sort, asc, from, size, &options).await?;
+            Ok((list, stats))
+        }
+
+        /// Search method returning the total, same as `search_with_options`, but also returns the total number of documents 
+        /// that meet query conditions, e.g. for "showing 20-40 of 5312908" in a paginated UI. 
+        /// The total computed by the deep paging calculation is reused, a request tracking the total is only sent when it was not computed. 
+        /// 
+        /// # Parameters
+        /// 
+        /// Same as `search_with_options`. 
+        /// 
+        /// # Return
+        /// A list of the documents, same as `search`, and the total number of documents that match the query. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let (page, total) = client.search_with_total("test_data_*", "", None, "id", true, 20, 20, &Default::default()).await?;
+        /// println!("showing 20-40 of {}", total);
+        /// # Ok(())
+        /// # }
+        /// ```
+        #[allow(clippy::too_many_arguments)]
+        pub async fn search_with_total(&self, index: &str, query: &str, source: Option<&Vec<&str>>, sort: &str, asc: bool, from: i64, size: i64, options: &SearchOptions<'_>) -> Result<(Vec<String>, i64), Error> {
+            let (list, total) = self.search_map_total::<i64, _, _>(index, query, source, sort, asc, from, size, options, |hit| Ok(EsJsonAnalyzer::to_json(hit))).await?;
+            if let Some(total) = total {
+                return Ok((list, total));
+            }
+            let query = Self::normalize_query(query)?;
+            let query = if options.skip_missing_sort { Self::build_exists_query(query, sort) } else { String::from(query) };
+            let resp = self.query(index, &query, None, &Self::build_sort(sort, None, asc, None), 0, 0, None, true, options).await?;
+            match resp.get_total()? {
+                Some(total) => Ok((list, total)),
+                None => Err(Error::invalid_json()),
+            }
+        }
+
+        /// Search below method, get the documents with sort values lower than the cursor, in descending order, 
+        /// e.g. the newest documents below the last seen sort value when tailing an index. 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `index`, `query`, `source`, `sort`, `size`: 
+        ///   Same as `search`. 
+        /// 
+        /// * `cursor`: 
+        ///   The upper bound of the sort values, exclusive. 
+        /// 
+        /// # Return
+        /// A list of the documents below the cursor, highest sort value first. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let result = client.search_below("test_data_*", "", None, "id", 100000000, 1000).await;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn search_below(&self, index: &str, query: &str, source: Option<&Vec<&str>>, sort: &str, cursor: i64, size: i64) -> Result<Vec<String>, Error> {
+            let query = Self::build_cmp_query(Self::normalize_query(query)?, sort, "lt", cursor);
+            self.search(index, &query, source, sort, false, 0, size).await
+        }
+
+        /// Search above method, get the documents with sort values higher than the cursor, in ascending order. 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `index`, `query`, `source`, `sort`, `size`: 
+        ///   Same as `search`. 
+        /// 
+        /// * `cursor`: 
+        ///   The lower bound of the sort values, exclusive. 
+        /// 
+        /// # Return
+        /// A list of the documents above the cursor, lowest sort value first. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let result = client.search_above("test_data_*", "", None, "id", 100000000, 1000).await;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn search_above(&self, index: &str, query: &str, source: Option<&Vec<&str>>, sort: &str, cursor: i64, size: i64) -> Result<Vec<String>, Error> {
+            let query = Self::build_cmp_query(Self::normalize_query(query)?, sort, "gt", cursor);
+            self.search(index, &query, source, sort, true, 0, size).await
+        }
+
+        /// Cursor search method, get the next documents after a cursor returned by a previous call, 
+        /// so a scan can be continued later, even in another process, without computing from. 
+        /// The cursor is only valid while the sort values of the documents do not change, documents inserted behind the 
+        /// cursor are not returned. 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `index`, `query`, `source`, `sort`, `size`: 
+        ///   Same as `search`. 
+        /// 
+        /// * `asc`: 
+        ///   Sort order of the first call, the sort order of a cursor is kept when continuing from it. 
+        /// 
+        /// * `cursor`: 
+        ///   The cursor returned by the previous call, None to start from the first document. 
+        /// 
+        /// # Return
+        /// A list of the documents after the cursor, and the cursor after the last document, 
+        /// None when no documents are returned. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let (docs, cursor) = client.search_cursor("test_data_*", "", None, "id", true, None, 1000).await?;
+        /// let token = cursor.unwrap().to_string();
+        /// // later
+        /// let cursor = token.parse::<deep_page_client::Cursor>()?;
+        /// let (docs, cursor) = client.search_cursor("test_data_*", "", None, "id", true, Some(&cursor), 1000).await?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        #[allow(clippy::too_many_arguments)]
+        pub async fn search_cursor(&self, index: &str, query: &str, source: Option<&Vec<&str>>, sort: &str, asc: bool, cursor: Option<&Cursor>, size: i64) -> Result<(Vec<String>, Option<Cursor>), Error> {
+            let query = Self::normalize_query(query)?;
+            let (query, asc) = match cursor {
+                Some(cursor) => (Self::build_cmp_query(query, sort, if cursor.asc { "gt" } else { "lt" }, cursor.last_sort), cursor.asc),
+                None => (String::from(query), asc),
+            };
+            let items = self.search_map::<i64, _, _>(index, &query, source, sort, asc, 0, size, &SearchOptions::default(), |hit| {
+                Ok((EsJsonAnalyzer::to_json(hit), Self::hit_sort_value::<i64>(hit, sort)?))
+            }).await?;
+            let cursor = items.last().map(|(_, last_sort)| Cursor { last_sort: *last_sort, asc });
+            Ok((items.into_iter().map(|(doc, _)| doc).collect(), cursor))
+        }
+
+        /// Paginate method, create a `PageIterator` that fetches the documents one page per call of `PageIterator::next_page`, 
+        /// for callers that drive each round trip themselves, e.g. a command line tool printing page by page. 
+        /// Each page continues after the sort value of the last document of the previous page, same as `search_cursor`. 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `index`, `query`, `source`, `sort`, `asc`: 
+        ///   Same as `search`. 
+        /// 
+        /// * `page_size`: 
+        ///   The number of documents of each page, a non-negative number. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let mut pages = client.paginate("test_data_*", "", None, "id", true, 1000);
+        /// while let Some(page) = pages.next_page().await? {
+        ///     println!("{} documents", page.len());
+        /// }
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn paginate<'a>(&'a self, index: &'a str, query: &'a str, source: Option<&'a Vec<&'a str>>, sort: &'a str, asc: bool, page_size: i64) -> PageIterator<'a> {
+            PageIterator { client: self, index, query, source, sort, asc, page_size, cursor: None, done: false }
+        }
+
+        /// Resumable search method, same as `search`, but when a request fails in the middle of the search, the documents 
+        /// gathered so far are returned with the error, and a cursor to continue from with `search_cursor`. 
+        /// Documents are always fetched in the requested order, the reverse query direction optimization of `search` is not used, 
+        /// so the gathered documents are always the beginning of the requested ones. 
+        /// 
+        /// # Parameters
+        /// 
+        /// Same as `search`. 
+        /// 
+        /// # Return
+        /// A list of the documents gathered, the error that stopped the search, None when the search completed, 
+        /// and the cursor after the last gathered document, None when the search completed or no documents were gathered. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # fn write(docs: &[String]) -> std::io::Result<()> { Ok(()) }
+        /// # fn checkpoint(cursor: &str, done: usize) -> std::io::Result<()> { Ok(()) }
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let (docs, error, cursor) = client.search_resumable("test_data_*", "", None, "id", true, 0, 10000000).await;
+        /// write(&docs)?;
+        /// if let (Some(error), Some(cursor)) = (error, cursor) {
+        ///     checkpoint(&cursor.to_string(), docs.len())?;
+        ///     return Err(error.into());
+        /// }
+        /// // later
+        /// # let (token, done) = (String::new(), docs.len() as i64);
+        /// let cursor = token.parse::<deep_page_client::Cursor>()?;
+        /// let (docs, cursor) = client.search_cursor("test_data_*", "", None, "id", true, Some(&cursor), 10000000 - done).await?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        #[allow(clippy::too_many_arguments)]
+        pub async fn search_resumable(&self, index: &str, query: &str, source: Option<&Vec<&str>>, sort: &str, asc: bool, from: i64, size: i64) -> (Vec<String>, Option<Error>, Option<Cursor>) {
+            let options = SearchOptions::default();
+            let mut traversal = match self.plan::<i64>(index, query, sort, asc, from, size, false, false, &options).await {
+                Ok(Some(traversal)) => traversal,
+                Ok(None) => return (vec![], None, None),
+                Err(e) => return (vec![], Some(e), None),
+            };
+            let mut list = vec![];
+            let mut last_sort = None;
+            loop {
+                let error = match self.next_batch::<i64>(index, source, &options, &mut traversal).await {
+                    Ok(Some(batch)) => match Self::collect_batch(&batch, sort, &mut list, &mut last_sort) {
+                        Ok(()) => continue,
+                        Err(e) => e,
+                    },
+                    Ok(None) => return (list, None, None),
+                    Err(e) => e,
+                };
+                let cursor = last_sort.map(|last_sort| Cursor { last_sort, asc: traversal.asc });
+                return (list, Some(error), cursor);
+            }
+        }
+
+        /// Add the documents of a batch to the list, and keep the sort value of the last one. 
+        fn collect_batch(batch: &EsJson, sort: &str, list: &mut Vec<String>, last_sort: &mut Option<i64>) -> Result<(), Error> {
+            let hits = batch.get_hits()?;
+            if let Some(last) = hits.last() {
+                *last_sort = Some(Self::hit_sort_value::<i64>(last, sort)?);
+            }
+            list.extend(hits.iter().map(EsJsonAnalyzer::to_json));
+            Ok(())
+        }
+
+        /// Resume search method, continue a scan over a point in time from a token, 
+        /// e.g. stored in the checkpoint of a job, so the job can be resumed exactly even in another process. 
+        /// All the documents are read from the frozen view of the point in time, so documents written during the scan 
+        /// are neither returned nor shifting the position of the token. 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `token`: 
+        ///   The token returned by the previous call, or a new token created by `ResumeToken::new` to start the scan. 
+        /// 
+        /// * `size`: 
+        ///   The number of hits to return. a non-negative number. 
+        /// 
+        /// # Return
+        /// A list of the documents after the token, same as `search`, and the token after the last document, 
+        /// holding the latest point in time id returned by elasticsearch, None when no documents are returned. 
+        /// `Error::PitExpired` is returned when the point in time has expired. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # fn write(docs: &[String]) -> std::io::Result<()> { Ok(()) }
+        /// # fn checkpoint(token: Option<String>) -> std::io::Result<()> { Ok(()) }
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let pit = client.open_pit("test_data_*", "10m").await?;
+        /// let mut token = Some(deep_page_client::ResumeToken::new(&pit, "10m", "", "id", true));
+        /// while let Some(current) = token {
+        ///     let (docs, next) = client.search_resume(&current, 10000).await?;
+        ///     write(&docs)?;
+        ///     checkpoint(next.as_ref().map(|t| t.to_string()))?;
+        ///     token = next;
+        /// }
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn search_resume(&self, token: &ResumeToken, size: i64) -> Result<(Vec<String>, Option<ResumeToken>), Error> {
+            let query = Self::normalize_query(&token.query)?;
+            let (query, asc) = match token.cursor {
+                Some(cursor) => (Self::build_cmp_query(query, &token.sort, if cursor.asc { "gt" } else { "lt" }, cursor.last_sort), cursor.asc),
+                None => (String::from(query), token.asc),
+            };
+            let options = SearchOptions { pit: Some(&token.pit), pit_keep_alive: Some(&token.keep_alive), ..Default::default() };
+            let mut traversal = match self.plan::<i64>("", &query, &token.sort, asc, 0, size, false, false, &options).await? {
+                Some(traversal) => traversal,
+                None => return Ok((vec![], None)),
+            };
+            let mut list = vec![];
+            let mut last_sort = None;
+            while let Some(batch) = self.next_batch::<i64>("", None, &options, &mut traversal).await? {
+                Self::collect_batch(&batch, &token.sort, &mut list, &mut last_sort)?;
+            }
+            // the point in time id may change with each response, the token keeps the latest one.
+            let pit = traversal.pit.unwrap_or_else(|| token.pit.clone());
+            let next = last_sort.map(|last_sort| ResumeToken { pit, cursor: Some(Cursor { last_sort, asc }), ..token.clone() });
+            Ok((list, next))
+        }
+
+        /// Page method, call the searchAPI of elasticsearch once to fetch a single page of documents, without the count, probe 
+        /// and binary search requests of `search`. 
+        /// The page must be within the max_result_window setting of the index, otherwise the error response of elasticsearch is returned. 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `index`, `query`, `source`, `sort`, `asc`: 
+        ///   Same as `search`, but the sort field does not need to be a unique number field. 
+        /// 
+        /// * `from`: 
+        ///   Starting document offset, how many documents to skip. a non-negative number. 
+        /// 
+        /// * `size`: 
+        ///   The number of hits to return. a non-negative number. 
+        /// 
+        /// # Return
+        /// A list of the documents of the page, same as `search`. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let result = client.page("test_data_*", "", None, "id", true, 100, 20).await;
+        /// # Ok(())
+        /// # }
+        /// ```
+        #[allow(clippy::too_many_arguments)]
+        pub async fn page(&self, index: &str, query: &str, source: Option<&Vec<&str>>, sort: &str, asc: bool, from: i64, size: i64) -> Result<Vec<String>, Error> {
+            if index.is_empty() {
+                return Err(Error::InvalidParameter(String::from("index can not be empty.")));
+            }
+            if sort.is_empty() {
+                return Err(Error::InvalidParameter(String::from("sort can not be empty.")));
+            }
+            if from < 0 || size < 0 {
+                return Err(Error::InvalidParameter(String::from("from and size can not be negative.")));
+            }
+            let query = Self::normalize_query(query)?;
+            let options = SearchOptions::default();
+            let json = self.query(index, query, Self::build_source(source, None).as_deref(), &Self::build_sort(sort, None, asc, None), from, size, None, false, &options).await?;
+            Ok(json.get_hits()?.iter().map(EsJsonAnalyzer::to_json).collect())
+        }
+
+        /// Raw search method, call the searchAPI of elasticsearch once, same as `page`, but returns the complete response 
+        /// of elasticsearch untouched, e.g. to inspect the scores, shards and took time when debugging mappings and queries. 
+        /// Failed shards and timed out responses are returned as they are, they are not errors of this method. 
+        /// 
+        /// # Parameters
+        /// 
+        /// Same as `page`. 
+        /// 
+        /// # Return
+        /// The json formatted response of the searchAPI. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let resp = client.search_raw("test_data_*", "", None, "id", true, 100, 20).await?;
+        /// println!("{}", resp);
+        /// # Ok(())
+        /// # }
+        /// ```
+        #[allow(clippy::too_many_arguments)]
+        pub async fn search_raw(&self, index: &str, query: &str, source: Option<&Vec<&str>>, sort: &str, asc: bool, from: i64, size: i64) -> Result<String, Error> {
+            if index.is_empty() {
+                return Err(Error::InvalidParameter(String::from("index can not be empty.")));
+            }
+            if sort.is_empty() {
+                return Err(Error::InvalidParameter(String::from("sort can not be empty.")));
+            }
+            if from < 0 || size < 0 {
+                return Err(Error::InvalidParameter(String::from("from and size can not be negative.")));
+            }
+            let query = Self::normalize_query(query)?;
+            let options = SearchOptions::default();
+            self.query_raw(index, query, Self::build_source(source, None).as_deref(), &Self::build_sort(sort, None, asc, None), from, size, None, false, &options).await
+        }
+
+        /// Search method for a double unique number sort field, same as `search`, but the values of the `sort` field are 
+        /// double instead of long integer. The values must be finite and the value of each document must not be repeated. 
+        /// 
+        /// # Parameters
+        /// 
+        /// Same as `search`. 
+        /// 
+        /// # Return
+        /// A list of all documents that match the query. Each document is a json formatted string. 
+        /// If no documents match the query, an empty list is returned. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let result = client.search_f64("test_data_*", "", None, "score_id", true, 100000000, 10000).await;
+        /// # Ok(())
+        /// # }
+        /// ```
+        #[allow(clippy::too_many_arguments)]
+        pub async fn search_f64(&self, index: &str, query: &str, source: Option<&Vec<&str>>, sort: &str, asc: bool, from: i64, size: i64) -> Result<Vec<String>, Error> {
+            self.search_map::<f64, _, _>(index, query, source, sort, asc, from, size, &SearchOptions::default(), |hit| Ok(EsJsonAnalyzer::to_json(hit))).await
+        }
+
+        /// Search method for an unsigned long unique number sort field, same as `search`, but the values of the `sort` field are 
+        /// unsigned long integers, which can exceed the maximum of long integer, e.g. ids generated by a Snowflake-style scheme. 
+        /// 
+        /// # Parameters
+        /// 
+        /// Same as `search`. 
+        /// 
+        /// # Return
+        /// A list of all documents that match the query. Each document is a json formatted string. 
+        /// If no documents match the query, an empty list is returned. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let result = client.search_u64("test_data_*", "", None, "snowflake_id", true, 100000000, 10000).await;
+        /// # Ok(())
+        /// # }
+        /// ```
+        #[allow(clippy::too_many_arguments)]
+        pub async fn search_u64(&self, index: &str, query: &str, source: Option<&Vec<&str>>, sort: &str, asc: bool, from: i64, size: i64) -> Result<Vec<String>, Error> {
+            self.search_map::<u64, _, _>(index, query, source, sort, asc, from, size, &SearchOptions::default(), |hit| Ok(EsJsonAnalyzer::to_json(hit))).await
+        }
+
+        /// Search method returning hit metadata, same as `search`, but each document is returned as a `Hit` with its 
+        /// "_id", "_index" and "_score" alongside the "_source". 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `index`, `query`, `source`, `sort`, `asc`, `from`, `size`: 
+        ///   Same as `search`. 
+        /// 
+        /// * `options`: 
+        ///   Optional parameters of the query, see `SearchOptions`. 
+        /// 
+        /// # Return
+        /// A list of all hits that match the query. 
+        /// If no documents match the query, an empty list is returned. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let hits = client.search_hits("test_data_*", "", None, "id", true, 100000000, 10000, &Default::default()).await?;
+        /// for hit in hits {
+        ///     println!("{} {}", hit.id, hit.source);
+        /// }
+        /// # Ok(())
+        /// # }
+        /// ```
+        #[allow(clippy::too_many_arguments)]
+        pub async fn search_hits(&self, index: &str, query: &str, source: Option<&Vec<&str>>, sort: &str, asc: bool, from: i64, size: i64, options: &SearchOptions<'_>) -> Result<Vec<Hit>, Error> {
+            self.search_map::<i64, _, _>(index, query, source, sort, asc, from, size, options, Hit::from_json).await
+        }
+
+        /// Typed search method, same as `search`, but deserializes the "_source" field of each document into `T`. 
+        /// Requires the `serde` feature. 
+        /// 
+        /// # Parameters
+        /// 
+        /// Same as `search`. 
+        /// 
+        /// # Return
+        /// A list of the "_source" of all documents that match the query, deserialized into `T`. 
+        /// If no documents match the query, an empty list is returned. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// #[derive(serde::Deserialize)]
+        /// struct Data { id: i64, name: String }
+        /// 
+        /// let result = client.search_as::<Data>(
+        ///     "test_data_*", 
+        ///     "{\"match_all\":{}}",
+        ///     None, 
+        ///     "id", 
+        ///     true, 
+        ///     100000000, 
+        ///     10000).await;
+        /// # Ok(())
+        /// # }
+        /// ```
+        #[cfg(feature = "serde")]
+        #[allow(clippy::too_many_arguments)]
+        pub async fn search_as<T: serde::de::DeserializeOwned>(&self, index: &str, query: &str, source: Option<&Vec<&str>>, sort: &str, asc: bool, from: i64, size: i64) -> Result<Vec<T>, Error> {
+            self.search_map::<i64, _, _>(index, query, source, sort, asc, from, size, &SearchOptions::default(), |hit| {
+                T::deserialize(hit.find_json("\"_source\"")?).map_err(|e| Error::Parse { position: None, detail: format!("Deserialize error: {}", e) })
+            }).await
+        }
+
+        /// Search method with json values, same as `search`, but the `query`, `source` and `sort` parameters are 
+        /// `serde_json::Value`s instead of hand-built strings. Requires the `serde` feature. 
+        /// The values are serialized by serde_json, so the query spliced into the range restrictions is always well-formed. 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `query`: 
+        ///   Query Dsl for query, e.g. json!({"match_all":{}}). Null means all documents. 
+        /// 
+        /// * `source`: 
+        ///   source filter for query, an array of strings, e.g. json!(["column1", "obj1.*"]). 
+        /// 
+        /// * `sort`: 
+        ///   The unique number sort field, a string, e.g. json!("id"). 
+        /// 
+        /// * `index`, `asc`, `from`, `size`: 
+        ///   Same as `search`. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # use serde_json::json;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let result = client.search_value("test_data_*", &json!({"term":{"status":"ok"}}), None, &json!("id"), true, 100000000, 10000).await;
+        /// # Ok(())
+        /// # }
+        /// ```
+        #[cfg(feature = "serde")]
+        #[allow(clippy::too_many_arguments)]
+        pub async fn search_value(&self, index: &str, query: &serde_json::Value, source: Option<&serde_json::Value>, sort: &serde_json::Value, asc: bool, from: i64, size: i64) -> Result<Vec<String>, Error> {
+            let query = if query.is_null() { String::new() } else { query.to_string() };
+            let source = match source {
+                Some(serde_json::Value::Array(fields)) => Some(fields.iter()
+                    .map(|field| field.as_str().ok_or_else(|| Error::InvalidParameter(String::from("source must be an array of strings."))))
+                    .collect::<Result<Vec<&str>, Error>>()?),
+                Some(_) => return Err(Error::InvalidParameter(String::from("source must be an array of strings."))),
+                None => None,
+            };
+            let sort = match sort.as_str() {
+                Some(sort) => sort,
+                None => return Err(Error::InvalidParameter(String::from("sort must be a string."))),
+            };
+            self.search(index, &query, source.as_ref(), sort, asc, from, size).await
+        }
+
+        /// Streaming search method, same as `search`, but yields each document as batches arrive from elasticsearch 
+        /// instead of collecting all documents into a list. Requires the `stream` feature. 
+        /// The next batch is only fetched when the consumer polls past the current one, so memory usage is bounded by 
+        /// the batch_size setting rather than the `size` parameter. 
+        /// Documents are always fetched in the requested order, the reverse query direction optimization of `search` is not used. 
+        /// 
+        /// # Parameters
+        /// 
+        /// Same as `search`. 
+        /// 
+        /// # Return
+        /// A stream of all documents that match the query, each document is a json formatted string. 
+        /// The stream ends after the first error. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # use std::io::Write;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// # let mut writer = std::io::sink();
+        /// use futures::TryStreamExt;
+        /// 
+        /// let mut stream = Box::pin(client.search_stream("test_data_*", "", None, "id", true, 100000000, 10000000));
+        /// while let Some(doc) = stream.try_next().await? {
+        ///     writer.write_all(doc.as_bytes())?;
+        /// }
+        /// # Ok(())
+        /// # }
+        /// ```
+        #[cfg(feature = "stream")]
+        #[allow(clippy::too_many_arguments)]
+        pub fn search_stream<'a>(&'a self, index: &'a str, query: &'a str, source: Option<&'a Vec<&'a str>>, sort: &'a str, asc: bool, from: i64, size: i64) -> impl futures::Stream<Item = Result<String, Error>> + 'a {
+            use futures::TryStreamExt;
+
+            let batches = futures::stream::try_unfold(None, move |traversal: Option<Traversal>| async move {
+                let mut traversal = match traversal {
+                    Some(traversal) => traversal,
+                    None => match self.plan::<i64>(index, query, sort, asc, from, size, false, false, &SearchOptions::default()).await? {
+                        Some(traversal) => traversal,
+                        None => return Ok(None),
+                    },
+                };
+                match self.next_batch::<i64>(index, source, &SearchOptions::default(), &mut traversal).await? {
+                    Some(batch) => {
+                        let docs = batch.get_hits()?.iter().map(EsJsonAnalyzer::to_json).collect::<Vec<String>>();
+                        Ok(Some((docs, Some(traversal))))
+                    }
+                    None => Ok(None),
+                }
+            });
+            batches.map_ok(|docs| futures::stream::iter(docs.into_iter().map(Ok))).try_flatten()
+        }
+
+        /// Search into method, same as `search`, but sends each document to a channel as batches arrive from elasticsearch 
+        /// instead of collecting all documents into a list, so the consumer and the fetching run concurrently. 
+        /// Each send is awaited, so a bounded channel throttles the fetching to the pace of the consumer. 
+        /// Documents are always fetched in the requested order, the reverse query direction optimization of `search` is not used. 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `index`, `query`, `source`, `sort`, `asc`, `from`, `size`: 
+        ///   Same as `search`. 
+        /// 
+        /// * `sink`: 
+        ///   The sender of the channel receiving the documents, each document is a json formatted string. 
+        ///   The search fails when the receiver is dropped. 
+        /// 
+        /// # Return
+        /// The number of documents sent. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # use std::io::Write;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// # let mut writer = std::io::sink();
+        /// let (sender, mut receiver) = tokio::sync::mpsc::channel::<String>(10000);
+        /// let consumer = tokio::spawn(async move {
+        ///     while let Some(doc) = receiver.recv().await {
+        ///         writer.write_all(doc.as_bytes())?;
+        ///     }
+        ///     Ok::<(), std::io::Error>(())
+        /// });
+        /// let sent = client.search_into("test_data_*", "", None, "id", true, 100000000, 10000000, sender).await?;
+        /// consumer.await??;
+        /// # Ok(())
+        /// # }
+        /// ```
+        #[allow(clippy::too_many_arguments)]
+        pub async fn search_into(&self, index: &str, query: &str, source: Option<&Vec<&str>>, sort: &str, asc: bool, from: i64, size: i64, sink: tokio::sync::mpsc::Sender<String>) -> Result<usize, Error> {
+            let options = SearchOptions::default();
+            let mut traversal = match self.plan::<i64>(index, query, sort, asc, from, size, false, false, &options).await? {
+                Some(traversal) => traversal,
+                None => return Ok(0),
+            };
+            let mut sent = 0;
+            while let Some(batch) = self.next_batch::<i64>(index, source, &options, &mut traversal).await? {
+                for hit in batch.get_hits()? {
+                    if sink.send(EsJsonAnalyzer::to_json(hit)).await.is_err() {
+                        return Err(Error::Message(format!("receiver closed after {} documents", sent)));
+                    }
+                    sent += 1;
+                }
+            }
+            Ok(sent)
+        }
+
+        /// Search after method, pages forward through the documents that match the query using the native "search_after" 
+        /// cursor of elasticsearch, without a "from" parameter. 
+        /// Unlike `search`, the sort field does not need to be numeric, e.g. a keyword field that stores UUIDs can be used. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/paginate-search-results.html#search-after 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `index`, `query`, `source`: 
+        ///   Same as `search`. 
+        /// 
+        /// * `sort`: 
+        ///   The sort field for query, e.g. "uuid". This will be placed in the "sort" field of the request body. 
+        ///   All the documents to be queried must have a unique value in this field, any sortable field type can be used. 
+        /// 
+        /// * `asc`: 
+        ///   Sort order of the unique field, if true, means ascending, if false, means descending. 
+        /// 
+        /// * `size`: 
+        ///   The number of hits to return, starting from the first document in sort order. a non-negative number. 
+        /// 
+        /// # Return
+        /// A list of all documents that match the query. Each document is a json formatted string. 
+        /// If no documents match the query, an empty list is returned. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let result = client.search_after_key("test_data_*", "", None, "uuid", true, 100000).await;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn search_after_key(&self, index: &str, query: &str, source: Option<&Vec<&str>>, sort: &str, asc: bool, size: i64) -> Result<Vec<String>, Error> {
+            
+            // validate parameters
+            if index.is_empty() {
+                return Err(Error::InvalidParameter(String::from("index can not be empty.")));
+            }
+            if sort.is_empty() {
+                return Err(Error::InvalidParameter(String::from("sort can not be empty.")));
+            }
+            if size < 0 {
+                return Err(Error::InvalidParameter(String::from("size can not be negative.")));
+            }
+
+            let query = Self::normalize_query(query)?;
+            let mut search_after: Option<String> = None;
+            let mut remain_size = size;
+            let mut list = vec![];
+            while remain_size > 0 {
+                let retrieve_size = remain_size.min(self.batch_size);
+                let batch = self.query(index, query, Self::build_source(source, None).as_deref(), &Self::build_sort(sort, None, asc, None), 0, retrieve_size, search_after.as_deref(), false, &SearchOptions::default()).await?;
+                let hits = batch.get_hits()?;
+                if hits.is_empty() {
+                    break;
+                }
+                list.extend(hits.iter().map(EsJsonAnalyzer::to_json));
+                remain_size -= hits.len() as i64;
+
+                // Continue after the sort values of the last document.
+                let last_item = hits.last().unwrap();
+                search_after = Some(EsJsonAnalyzer::to_json(last_item.find_json("\"sort\"")?));
+            }
+
+            Ok(list)
+        }
+
+        /// Scroll method, call the scrollAPI of elasticsearch to retrieve all documents that meet query conditions in index order. 
+        /// This is a fallback for clusters without point in time support, or for indices without a numeric sort field, where neither `search` nor `search_after_key` can be used. 
+        /// The scroll context is cleared when the scan ends, and also when it stops early because of an error, 
+        /// if the returned future is dropped before it completes, the context is cleared in the background. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/paginate-search-results.html#scroll-search-results 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `index`, `query`, `source`: 
+        ///   Same as `search`. 
+        /// 
+        /// * `scroll_timeout`: 
+        ///   How long the scroll context is kept alive between two batches, an elasticsearch time value, e.g. "1m". 
+        /// 
+        /// * `size`: 
+        ///   The number of hits to return in each batch, a positive number, limited by the max_result_window setting of the index. 
+        /// 
+        /// # Return
+        /// A list of all documents that match the query. Each document is a json formatted string. 
+        /// If no documents match the query, an empty list is returned. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let result = client.scroll("test_data_*", "", None, "1m", 1000).await;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn scroll(&self, index: &str, query: &str, source: Option<&Vec<&str>>, scroll_timeout: &str, size: i64) -> Result<Vec<String>, Error> {
+
+            // validate parameters
+            if index.is_empty() {
+                return Err(Error::InvalidParameter(String::from("index can not be empty.")));
+            }
+            if scroll_timeout.is_empty() {
+                return Err(Error::InvalidParameter(String::from("scroll_timeout can not be empty.")));
+            }
+            if !is_time_value(scroll_timeout) {
+                return Err(Error::InvalidParameter(format!("scroll_timeout {} is not a time value, e.g. 1m.", scroll_timeout)));
+            }
+            if size <= 0 {
+                return Err(Error::InvalidParameter(String::from("size must be positive.")));
+            }
+
+            let query = Self::normalize_query(query)?;
+            let url = format!("{}/_search?scroll={}", index, encode_url_component(scroll_timeout));
+            let mut body = format!("{{\"query\":{},\"sort\":[\"_doc\"],", query);
+            if let Some(source) = Self::build_source(source, None) {
+                let _ = write!(body, "\"_source\":{},", source);
+            }
+            let _ = write!(body, "\"size\":{}}}", size);
+            let mut resp = self.post(&url, &body, None).await?;
+
+            let mut guard = ScrollGuard { transport: self.transport.clone(), headers: self.headers.clone(), scroll_id: None };
+            let result = self.scroll_pages(&mut resp, scroll_timeout, &mut guard).await;
+            if let Some(scroll_id) = guard.scroll_id.take() {
+                let cleared = self.request(Method::Delete, "_search/scroll", Some(&ScrollGuard::clear_body(&scroll_id)), None).await;
+                if let (Ok(_), Err(e)) = (&result, cleared) {
+                    return Err(e);
+                }
+            }
+            result
+        }
+
+        /// Scan all method, retrieve the documents that meet query conditions in index order, the fastest way to export an index. 
+        /// A point in time is opened for the scan, and the batches are fetched with the "_doc" sort and "search_after", 
+        /// so no sort field is needed and the deep paging calculation is not used. The order of the documents is not meaningful. 
+        /// The point in time is closed when the scan ends, also when it stops early because of an error. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/paginate-search-results.html#search-after 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `index`, `query`, `source`: 
+        ///   Same as `search`. 
+        /// 
+        /// * `size`: 
+        ///   The maximum number of documents to return, a non-negative number, e.g. i64::MAX for the whole index. 
+        /// 
+        /// # Return
+        /// A list of the documents that match the query, up to `size`. Each document is a json formatted string. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let result = client.scan_all("test_data_*", "", None, i64::MAX).await;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn scan_all(&self, index: &str, query: &str, source: Option<&Vec<&str>>, size: i64) -> Result<Vec<String>, Error> {
+            if size < 0 {
+                return Err(Error::InvalidParameter(String::from("size can not be negative.")));
+            }
+            let query = Self::normalize_query(query)?;
+            let mut pit = self.open_pit(index, SCAN_KEEP_ALIVE).await?;
+            let result = self.scan_pit(query, source, size, &mut pit).await;
+            let closed = self.close_pit(&pit).await;
+            if let (Ok(_), Err(e)) = (&result, closed) {
+                return Err(e);
+            }
+            result
+        }
+
+        /// Fetch the batches of `scan_all` from the point in time, the id of the point in time is updated from each response. 
+        async fn scan_pit(&self, query: &str, source: Option<&Vec<&str>>, size: i64, pit: &mut String) -> Result<Vec<String>, Error> {
+            let source = Self::build_source(source, None);
+            let mut search_after: Option<String> = None;
+            let mut remain_size = size;
+            let mut list = vec![];
+            while remain_size > 0 {
+                let retrieve_size = remain_size.min(self.batch_size);
+                let options = SearchOptions { pit: Some(pit.as_str()), pit_keep_alive: Some(SCAN_KEEP_ALIVE), ..Default::default() };
+                let batch = self.query("", query, source.as_deref(), "[\"_doc\"]", 0, retrieve_size, search_after.as_deref(), false, &options).await?;
+                if let Some(pit_id) = batch.find_optional_json("\"pit_id\"")? {
+                    *pit = pit_id.get_unescaped()?;
+                }
+                let hits = batch.get_hits()?;
+                if hits.is_empty() {
+                    break;
+                }
+                list.extend(hits.iter().map(EsJsonAnalyzer::to_json));
+                remain_size -= hits.len() as i64;
+
+                // Continue after the sort values of the last document, including the shard doc tie-breaker of the point in time.
+                let last_item = hits.last().unwrap();
+                search_after = Some(EsJsonAnalyzer::to_json(last_item.find_json("\"sort\"")?));
+            }
+            Ok(list)
+        }
+
+        /// Read the batches of a scroll, starting from the response that opened it. 
+        /// The latest scroll id is kept in the `guard`, so it can be cleared whatever the outcome is. 
+        async fn scroll_pages(&self, resp: &mut String, scroll_timeout: &str, guard: &mut ScrollGuard) -> Result<Vec<String>, Error> {
+            let mut list = vec![];
+            loop {
+                let json = self.parse_response(resp)?;
+                if let Ok(scroll_id) = json.find_json("\"_scroll_id\"") {
+                    guard.scroll_id = Some(scroll_id.get_unescaped()?);
+                }
+                if !self.allow_shard_failures {
+                    json.check_shards()?;
+                }
+                let hits = json.get_hits()?;
+                if hits.is_empty() {
+                    return Ok(list);
+                }
+                list.extend(hits.iter().map(EsJsonAnalyzer::to_json));
+
+                let scroll_id = match &guard.scroll_id {
+                    Some(scroll_id) => scroll_id,
+                    None => return Err(Error::Message(String::from("scroll response has no _scroll_id"))),
+                };
+                let body = format!("{{\"scroll\":{},\"scroll_id\":{}}}", json_string(scroll_timeout), json_string(scroll_id));
+                *resp = self.post("_search/scroll", &body, None).await?;
+            }
+        }
+
+        /// Explain plan method, compute how `search` would fetch the documents without fetching them, 
+        /// e.g. to check the number of round trips of an expensive deep scan before running it. 
+        /// Only the count, probe and binary search requests of the deep paging calculation are sent. 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `index`, `query`, `sort`, `asc`, `from`, `size`: 
+        ///   Same as `search`. 
+        /// 
+        /// # Return
+        /// The plan of the search, see `SearchPlan`. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let plan = client.explain_plan("test_data_*", "", "id", true, 100000000, 10000).await?;
+        /// println!("{} batches after {} planning requests", plan.batches, plan.requests);
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn explain_plan(&self, index: &str, query: &str, sort: &str, asc: bool, from: i64, size: i64) -> Result<SearchPlan, Error> {
+            let options = SearchOptions::default();
+            let mut traversal = self.plan::<i64>(index, query, sort, asc, from, size, true, true, &options).await?.unwrap_or_default();
+            self.finish_traversal(&mut traversal).await;
+            let first_size = traversal.remain_size.min(self.batch_size).min(self.max_result_window - traversal.next_from);
+            let batches = match traversal.remain_size {
+                0 => 0,
+                remain_size => {
+                    let rest = remain_size - first_size;
+                    1 + rest / self.batch_size + i64::from(rest % self.batch_size != 0)
+                }
+            };
+            Ok(SearchPlan {
+                reverse: traversal.reverse,
+                asc: traversal.asc,
+                new_from: traversal.next_from,
+                start: traversal.start,
+                query: traversal.next_query,
+                total: traversal.total,
+                requests: traversal.requests,
+                batches,
+            })
+        }
+
+        /// Count method, call the countAPI of elasticsearch to get the total number of documents that meet query conditions, without fetching any documents. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-count.html 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `index`: 
+        ///   The index name for query, can use wildcards, same as `search`. 
+        /// 
+        /// * `query`: 
+        ///   Query Dsl for query, this is a json formatted string, e.g. {"match_all":{}}. 
+        ///   If not specified, all documents in the index are counted. 
+        /// 
+        /// # Return
+        /// The total number of documents that match the query. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let total = client.count("test_data_*", "{\"match_all\":{}}").await;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn count(&self, index: &str, query: &str) -> Result<i64, Error> {
+            let query = Self::normalize_query(query)?;
+            self.count_with(index, query, &SearchOptions::default()).await
+        }
+
+        /// Exists method, check whether any document meets query conditions. 
+        /// The search terminates after the first matching document of each shard, so it is cheaper than `count`. 
+        /// It is sent like the batches of `search`, so shard failures and timed out responses fail the same way. 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `index`, `query`: 
+        ///   Same as `count`. 
+        /// 
+        /// # Return
+        /// True if at least one document matches the query. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let exists = client.exists("test_data_*", "{\"term\":{\"status\":\"failed\"}}").await?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn exists(&self, index: &str, query: &str) -> Result<bool, Error> {
+            if index.is_empty() {
+                return Err(Error::InvalidParameter(String::from("index can not be empty.")));
+            }
+            let query = Self::normalize_query(query)?;
+            let options = SearchOptions { terminate_after: Some(1), ..Default::default() };
+            let json = self.query(index, query, Some("false"), "\"_doc\"", 0, 1, None, false, &options).await?;
+            // a lower bound of the total still means that some document matches, without a total the hits tell it.
+            match json.get_total()? {
+                Some(total) => Ok(total > 0),
+                None => Ok(!json.get_hits()?.is_empty()),
+            }
+        }
+
+        /// Count ranges method, count the documents in each range of sort values, e.g. the deciles of the sort field, 
+        /// to see the distribution of the data before paging it. The ranges are restricted the same way as the binary search 
+        /// of the deep paging calculation, one count request per range. With `ClientBuilder::concurrency`, up to that many 
+        /// count requests are sent concurrently. 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `index`, `query`, `sort`: 
+        ///   Same as `search`. 
+        /// 
+        /// * `bounds`: 
+        ///   The ranges of sort values, each one is the minimum and the maximum value, both inclusive, e.g. [(0, 999), (1000, 1999)]. 
+        /// 
+        /// # Return
+        /// The number of documents in each range, in the order of `bounds`. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let counts = client.count_ranges("test_data_*", "", "id", &[(0, 999), (1000, 1999)]).await?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn count_ranges(&self, index: &str, query: &str, sort: &str, bounds: &[(i64, i64)]) -> Result<Vec<i64>, Error> {
+            if index.is_empty() {
+                return Err(Error::InvalidParameter(String::from("index can not be empty.")));
+            }
+            if sort.is_empty() {
+                return Err(Error::InvalidParameter(String::from("sort can not be empty.")));
+            }
+            if bounds.iter().any(|(start, end)| start > end) {
+                return Err(Error::InvalidParameter(String::from("the start of a range can not be greater than its end.")));
+            }
+            let query = Self::normalize_query(query)?;
+            let options = SearchOptions::default();
+            let range_query = RangeQuery::new(query, sort);
+            let queries = bounds.iter().map(|(start, end)| range_query.range(*start, *end));
+
+            #[cfg(feature = "parallel")]
+            {
+                use futures::{StreamExt, TryStreamExt};
+                let options = &options;
+                futures::stream::iter(queries)
+                    .map(|range_query| async move { self.count_with(index, &range_query, options).await })
+                    .buffered(self.concurrency)
+                    .try_collect::<Vec<i64>>()
+                    .await
+            }
+            #[cfg(not(feature = "parallel"))]
+            {
+                let mut counts = Vec::with_capacity(bounds.len());
+                for range_query in queries {
+                    counts.push(self.count_with(index, &range_query, &options).await?);
+                }
+                Ok(counts)
+            }
+        }
+
+        /// First document method, get the document with the lowest value of the sort field, 
+        /// e.g. for watermark bookkeeping in incremental pipelines. 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `index`, `query`: 
+        ///   Same as `search`. 
+        /// 
+        /// * `sort`: 
+        ///   The sort field, e.g. "id". 
+        /// 
+        /// # Return
+        /// The document with the lowest sort value as a json formatted string, same as `search`. 
+        /// None if no documents match the query. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let first = client.first_by_sort("test_data_*", "", "id").await?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn first_by_sort(&self, index: &str, query: &str, sort: &str) -> Result<Option<String>, Error> {
+            self.edge_by_sort(index, query, sort, true).await
+        }
+
+        /// Last document method, get the document with the highest value of the sort field, same as `first_by_sort`. 
+        /// 
+        /// # Parameters
+        /// 
+        /// Same as `first_by_sort`. 
+        /// 
+        /// # Return
+        /// The document with the highest sort value as a json formatted string, same as `search`. 
+        /// None if no documents match the query. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let last = client.last_by_sort("test_data_*", "", "id").await?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn last_by_sort(&self, index: &str, query: &str, sort: &str) -> Result<Option<String>, Error> {
+            self.edge_by_sort(index, query, sort, false).await
+        }
+
+        /// Get by sort method, get the document with the specified value of the unique number sort field, 
+        /// e.g. when the unique field is not the "_id" of the documents. 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `index`, `query`: 
+        ///   Same as `search`. 
+        /// 
+        /// * `sort`: 
+        ///   The unique number sort field, e.g. "id". 
+        /// 
+        /// * `value`: 
+        ///   The value of the sort field. 
+        /// 
+        /// # Return
+        /// The document with the value as a json formatted string, same as `search`. 
+        /// None if no documents match, an error if more than one document has the value. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let doc = client.get_by_sort("test_data_*", "", "id", 100000000).await?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn get_by_sort(&self, index: &str, query: &str, sort: &str, value: i64) -> Result<Option<String>, Error> {
+            if index.is_empty() {
+                return Err(Error::InvalidParameter(String::from("index can not be empty.")));
+            }
+            if sort.is_empty() {
+                return Err(Error::InvalidParameter(String::from("sort can not be empty.")));
+            }
+            let query = Self::build_range_query(Self::normalize_query(query)?, sort, value, value);
+            // fetch two documents, so that a second one reveals a violated uniqueness.
+            let items = self.query(index, &query, None, &Self::build_sort(sort, None, true, None), 0, 2, None, false, &SearchOptions::default()).await?;
+            match &items.get_hits()?[..] {
+                [] => Ok(None),
+                [item] => Ok(Some(EsJsonAnalyzer::to_json(item))),
+                _ => Err(Error::Message(format!("more than one document has sort value {} of {}.", value, sort))),
+            }
+        }
+
+        /// Get the first document in the sort order. 
+        async fn edge_by_sort(&self, index: &str, query: &str, sort: &str, asc: bool) -> Result<Option<String>, Error> {
+            if index.is_empty() {
+                return Err(Error::InvalidParameter(String::from("index can not be empty.")));
+            }
+            if sort.is_empty() {
+                return Err(Error::InvalidParameter(String::from("sort can not be empty.")));
+            }
+            let query = Self::normalize_query(query)?;
+            let item = self.query(index, query, None, &Self::build_sort(sort, None, asc, None), 0, 1, None, false, &SearchOptions::default()).await?;
+            Ok(item.get_hits()?.last().map(EsJsonAnalyzer::to_json))
+        }
+
+        /// Aggregate method, call the searchAPI of elasticsearch to aggregate the documents that meet query conditions, without fetching any documents. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-aggregations.html 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `index`: 
+        ///   The index name for query, can use wildcards, same as `search`. 
+        /// 
+        /// * `query`: 
+        ///   Query Dsl for query, this is a json formatted string, e.g. {"match_all":{}}. 
+        ///   If not specified, all documents in the index are aggregated. 
+        /// 
+        /// * `aggs`: 
+        ///   Aggregations Dsl, this is a json formatted string, e.g. {"by_user":{"terms":{"field":"user_id"}}}. 
+        ///   This will be placed in the "aggs" field of the request body. 
+        /// 
+        /// # Return
+        /// The "aggregations" field of the response, a json formatted string. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let aggregations = client.aggregate("test_data_*", "{\"match_all\":{}}", "{\"max_id\":{\"max\":{\"field\":\"id\"}}}").await;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn aggregate(&self, index: &str, query: &str, aggs: &str) -> Result<String, Error> {
+            if index.is_empty() {
+                return Err(Error::InvalidParameter(String::from("index can not be empty.")));
+            }
+            if aggs.is_empty() {
+                return Err(Error::InvalidParameter(String::from("aggs can not be empty.")));
+            }
+            let query = Self::normalize_query(query)?;
+            let url = format!("{}/_search", index);
+            let body = format!("{{\"query\":{},\"size\":0,\"aggs\":{}}}", query, aggs);
+            let resp = self.post(&url, &body, None).await?;
+            let json = self.parse_response(&resp)?;
+            if !self.allow_shard_failures {
+                json.check_shards()?;
+            }
+            match json.find_optional_json("\"aggregations\"")? {
+                Some(aggregations) => Ok(EsJsonAnalyzer::to_json(aggregations)),
+                None => Ok(String::from("{}")),
+            }
+        }
+
+        /// Search template method, call the search template API of elasticsearch to execute a stored search template. 
+        /// The template is executed as a single search request, it bypasses the deep paging optimization of `search`, 
+        /// so the from and size of the template are limited by the max_result_window setting of the index. 
+        /// Shard failures and timed out responses fail the same way as the batches of `search`. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/search-template.html 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `index`: 
+        ///   The index name for query, can use wildcards, same as `search`. 
+        /// 
+        /// * `template_id`: 
+        ///   The id of the stored search template. This will be placed in the "id" field of the request body. 
+        /// 
+        /// * `params`: 
+        ///   Parameters of the template, this is a json formatted string, e.g. {"user":"kimchy","from":0,"size":100}. 
+        ///   This will be placed in the "params" field of the request body. If not specified, no parameters are passed. 
+        /// 
+        /// # Return
+        /// A list of the documents returned by the template, same as `search`. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let result = client.search_template("test_data_*", "my-search-template", "{\"query_string\":\"hello\"}").await;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn search_template(&self, index: &str, template_id: &str, params: &str) -> Result<Vec<String>, Error> {
+            if index.is_empty() {
+                return Err(Error::InvalidParameter(String::from("index can not be empty.")));
+            }
+            if template_id.is_empty() {
+                return Err(Error::InvalidParameter(String::from("template_id can not be empty.")));
+            }
+            let params = if params.is_empty() {"{}"} else {params};
+            let options = SearchOptions::default();
+            options.check_cancelled()?;
+            let url = options.build_url(options.index_path(index, "_search/template"));
+            let body = format!("{{\"id\":{},\"params\":{}}}", json_string(template_id), params);
+            let resp = self.post(&url, &body, options.headers).await?;
+            options.record_request(body.len(), resp.len());
+            let json = self.parse_response(&resp)?;
+            self.check_search_response(&json)?;
+            Ok(json.get_hits()?.iter().map(EsJsonAnalyzer::to_json).collect())
+        }
+
+        /// Open point in time method, call the point in time API of elasticsearch to freeze a view of the index. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/point-in-time-api.html 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `index`: 
+        ///   The index name to open the point in time for, can use wildcards, same as `search`. 
+        /// 
+        /// * `keep_alive`: 
+        ///   How long the point in time is kept alive, an elasticsearch time value, e.g. "5m". 
+        /// 
+        /// # Return
+        /// The id of the point in time, pass it to `SearchOptions::pit` to search against it, and close it with `close_pit`. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let pit = client.open_pit("test_data_*", "5m").await?;
+        /// let options = deep_page_client::SearchOptions { pit: Some(&pit), pit_keep_alive: Some("5m"), ..Default::default() };
+        /// let result = client.search_with_options("", "", None, "id", true, 100000000, 10000, &options).await;
+        /// client.close_pit(&pit).await?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn open_pit(&self, index: &str, keep_alive: &str) -> Result<String, Error> {
+            if index.is_empty() {
+                return Err(Error::InvalidParameter(String::from("index can not be empty.")));
+            }
+            if keep_alive.is_empty() {
+                return Err(Error::InvalidParameter(String::from("keep_alive can not be empty.")));
+            }
+            if !is_time_value(keep_alive) {
+                return Err(Error::InvalidParameter(format!("keep_alive {} is not a time value, e.g. 5m.", keep_alive)));
+            }
+            let url = format!("{}/_pit?keep_alive={}", index, encode_url_component(keep_alive));
+            let resp = self.request(Method::Post, &url, None, None).await?;
+            let json = self.parse_response(&resp)?;
+            json.find_json("\"id\"")?.get_unescaped()
+        }
+
+        /// Close point in time method, releases the point in time opened by `open_pit`. 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `pit`: 
+        ///   The id of the point in time. 
+        pub async fn close_pit(&self, pit: &str) -> Result<(), Error> {
+            if pit.is_empty() {
+                return Err(Error::InvalidParameter(String::from("pit can not be empty.")));
+            }
+            let body = format!("{{\"id\":{}}}", json_string(pit));
+            self.request(Method::Delete, "_pit", Some(&body), None).await?;
+            Ok(())
+        }
+
+        /// Ping method, call the root API of elasticsearch to check that the cluster is reachable, 
+        /// e.g. to fail fast before a long batch job instead of failing in the middle of it. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// client.ping().await?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn ping(&self) -> Result<(), Error> {
+            self.request(Method::Get, "/", None, None).await?;
+            Ok(())
+        }
+
+        /// Health method, call the cluster health API of elasticsearch. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/cluster-health.html 
+        /// 
+        /// # Return
+        /// The status and node counts of the cluster. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let client = deep_page_client::doc_client();
+        /// let health = client.health().await?;
+        /// if health.status == "red" {
+        ///     return Err(format!("cluster {} is red", health.cluster_name).into());
+        /// }
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn health(&self) -> Result<ClusterHealth, Error> {
+            let resp = self.request(Method::Get, "_cluster/health", None, None).await?;
+            let json = self.parse_response(&resp)?;
+            Ok(ClusterHealth {
+                cluster_name: json.find_json("\"cluster_name\"")?.get_unescaped()?,
+                status: json.find_json("\"status\"")?.get_unescaped()?,
+                number_of_nodes: json.find_json("\"number_of_nodes\"")?.get_i64()?,
+                number_of_data_nodes: json.find_json("\"number_of_data_nodes\"")?.get_i64()?,
+            })
+        }
+
+        /// Refresh limits method, call the settings API of elasticsearch to read the "index.max_result_window" of the index, 
+        /// and derive the max_from, batch size and max_result_window of the client from it. 
+        /// The max_from becomes a fifth of the window, the batch size is capped at three tenths of the window, 
+        /// the same proportions as the defaults for the default window of 10000. 
+        /// When several indices are matched, the smallest window is used, when the setting is absent, the default of 10000 is used. 
+        /// Reference: https://www.elastic.co/guide/en/elasticsearch/reference/current/index-modules.html#index-max-result-window 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `index`: 
+        ///   The index name to read the settings of, can use wildcards, same as `search`. 
+        /// 
+        /// # Return
+        /// The max_result_window read from the settings. 
+        /// 
+        /// # Examples
+        /// 
+        /// ```no_run
+        /// # use es_deep_pager::deep_page_client;
+        /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+        /// # let transport = elasticsearch::http::transport::Transport::single_node("http://localhost:9200")?;
+        /// let mut client = deep_page_client::Client::new(transport);
+        /// let window = client.refresh_limits("test_data_*").await?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub async fn refresh_limits(&mut self, index: &str) -> Result<i64, Error> {
+            if index.is_empty() {
+                return Err(Error::InvalidParameter(String::from("index can not be empty.")));
+            }
+            let url = format!("{}/_settings/index.max_result_window?flat_settings=true&include_defaults=true", index);
+            let resp = self.request(Method::Get, &url, None, None).await?;
+            let json = self.parse_response(&resp)?;
+            let mut window: Option<i64> = None;
+            for (_, settings) in json.get_object()? {
+                // an explicit setting is under "settings", the default value of the cluster is under "defaults".
+                let mut value = None;
+                for section in ["\"settings\"", "\"defaults\""] {
+                    if let Some(section) = settings.find_optional_json(section)? {
+                        value = value.or(section.find_optional_json("\"index.max_result_window\"")?);
+                    }
+                }
+                if let Some(value) = value {
+                    let value = value.get_i64()?;
+                    window = Some(window.map_or(value, |window| window.min(value)));
+                }
+            }
+            let window = window.unwrap_or(DEFAULT_MAX_RESULT_WINDOW);
+            if window < 2 {
+                return Err(Error::Message(format!("max_result_window {} of {} is too small for paging.", window, index)));
+            }
+            self.max_result_window = window;
+            self.max_from = (window / 5).max(1);
+            self.batch_size = self.batch_size.min((window * 3 / 10).max(1));
+            Ok(window)
+        }
+
+        /// Get the total number of documents that meet query conditions. 
+        /// If a point in time or the shard request cache is specified in `options`, the total is read from a search with size 0, 
+        /// because the countAPI supports neither of them. 
+        /// The `query` must already be normalized by `normalize_query`, the user query is normalized once by the public methods, 
+        /// and the queries restricting it are built from the normalized one. 
+        #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(index = %index)))]
+        async fn count_with(&self, index: &str, query: &str, options: &SearchOptions<'_>) -> Result<i64, Error> {
+            options.check_cancelled()?;
+            let filtered_query;
+            let query = match options.post_filter {
+                Some(post_filter) => {
+                    filtered_query = format!("{{\"bool\":{{\"must\":{},\"filter\":{}}}}}", query, post_filter);
+                    filtered_query.as_str()
+                }
+                None => query,
+            };
+            if options.pit.is_some() || options.request_cache {
+                let (url, body) = match options.pit {
+                    Some(pit) => (String::from("_search"), format!("{{\"query\": {},{},\"size\":0,\"track_total_hits\":true}}", query, Self::build_pit(pit, options.pit_keep_alive))),
+                    None => (options.index_path(index, "_search"), format!("{{\"query\": {},\"size\":0,\"track_total_hits\":true}}", query)),
+                };
+                let resp = self.post(&options.build_url(url), &body, options.headers).await?;
+                options.record_request(body.len(), resp.len());
+                let json = self.parse_response(&resp)?;
+                return match json.get_total()? {
+                    Some(count) => Ok(count),
+                    None => Err(Error::invalid_json()),
+                };
+            }
+            let url = options.build_url(options.index_path(index, "_count"));
+            let body = format!("{{\"query\": {}}}", query);
+            let resp = self.post(&url, &body, options.headers).await?;
+            options.record_request(body.len(), resp.len());
+            let json = self.parse_response(&resp)?;
+            json.find_json("\"count\"")?.get_i64()
+        }
+
+        /// Deep paging traversal shared by the search methods, `map` converts each hit to the returned item. 
+        #[allow(clippy::too_many_arguments)]
+        async fn search_map<V, T, F>(&self, index: &str, query: &str, source: Option<&Vec<&str>>, sort: &str, asc: bool, from: i64, size: i64, options: &SearchOptions<'_>, map: F) -> Result<Vec<T>, Error>
+        where V: SortValue, F: Fn(&EsJson) -> Result<T, Error> {
+            let (list, _) = self.search_map_total::<V, T, F>(index, query, source, sort, asc, from, size, options, map).await?;
+            Ok(list)
+        }
+
+        /// Same as `search_map`, but also returns the total number of documents that meet query conditions, 
+        /// if it was fetched by the deep paging calculation. 
+        #[allow(clippy::too_many_arguments)]
+        async fn search_map_total<V, T, F>(&self, index: &str, query: &str, source: Option<&Vec<&str>>, sort: &str, asc: bool, from: i64, size: i64, options: &SearchOptions<'_>, map: F) -> Result<(Vec<T>, Option<i64>), Error>
+        where V: SortValue, F: Fn(&EsJson) -> Result<T, Error> {
+            if source.is_some_and(|source| source.iter().any(|field| field.contains(['\"', '\\']))) {
+                return Err(Error::InvalidParameter(String::from("source is not valid JSON")));
+            }
+            if options.docvalue_fields.is_some_and(|fields| fields.iter().any(|field| field.contains(['\"', '\\']))) {
+                return Err(Error::InvalidParameter(String::from("docvalue_fields is not valid JSON")));
+            }
+            if options.fields.is_some_and(|fields| fields.iter().any(|field| field.contains(['\"', '\\']))) {
+                return Err(Error::InvalidParameter(String::from("fields is not valid JSON")));
+            }
+            if (options.check_sort_type || options.preflight) && !index.is_empty() {
+                self.check_field_caps(index, sort, options).await.map_err(|e| e.in_phase("field-caps"))?;
+            }
+            #[cfg(feature = "parallel")]
+            if self.concurrency > 1 && size > self.batch_size && options.collapse.is_none() && options.min_score.is_none() && !options.partial_on_deadline {
+                return self.search_parallel::<V, T, F>(index, query, source, sort, asc, from, size, options, &map).await;
+            }
+            self.search_sequential::<V, T, F>(index, query, source, sort, asc, from, size, options, &map).await
+        }
+
+        /// Split the requested documents into consecutive segments of from and size, and search the segments concurrently. 
+        /// Each segment locates its own starting sort value, so the concatenated segments are the same as a sequential search. 
+        #[cfg(feature = "parallel")]
+        #[allow(clippy::too_many_arguments)]
+        async fn search_parallel<V, T, F>(&self, index: &str, query: &str, source: Option<&Vec<&str>>, sort: &str, asc: bool, from: i64, size: i64, options: &SearchOptions<'_>, map: &F) -> Result<(Vec<T>, Option<i64>), Error>
+        where V: SortValue, F: Fn(&EsJson) -> Result<T, Error> {
+            use futures::{StreamExt, TryStreamExt};
+
+            let concurrency = self.concurrency as i64;
+            let segment_size = ((size - 1) / concurrency + 1).max(self.batch_size);
+            let mut segments = vec![];
+            let mut offset = 0;
+            while offset < size {
+                // an overflowing segment is rejected by the validation of its own search.
+                segments.push((from.saturating_add(offset), segment_size.min(size - offset)));
+                offset += segment_size;
+            }
+
+            let lists = futures::stream::iter(segments)
+                .map(|(from, size)| self.search_sequential::<V, T, F>(index, query, source, sort, asc, from, size, options, map))
+                .buffered(self.concurrency)
+                .try_collect::<Vec<(Vec<T>, Option<i64>)>>()
+                .await?;
+            let total = lists.iter().find_map(|(_, total)| *total);
+            Ok((lists.into_iter().flat_map(|(list, _)| list).collect(), total))
+        }
+
+        /// Search the documents batch by batch. 
+        #[allow(clippy::too_many_arguments)]
+        #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(index = %index, from = from, size = size)))]
+        async fn search_sequential<V, T, F>(&self, index: &str, query: &str, source: Option<&Vec<&str>>, sort: &str, asc: bool, from: i64, size: i64, options: &SearchOptions<'_>, map: &F) -> Result<(Vec<T>, Option<i64>), Error>
+        where V: SortValue, F: Fn(&EsJson) -> Result<T, Error> {
+
+            #[cfg(feature = "tracing")]
+            let start = std::time::Instant::now();
+
+            // partial results must be the beginning of the requested documents, so the query direction is not reversed.
+            let mut traversal = match self.plan::<V>(index, query, sort, asc, from, size, !options.partial_on_deadline, true, options).await? {
+                Some(traversal) => traversal,
+                None => return Ok((vec![], None)),
+            };
+
+            let mut list = vec![];
+            // sort values and ids of the hits, to order the documents sharing a sort value by "_id".
+            let order_ties = traversal.lead + traversal.trail > 0;
+            let mut keys = vec![];
+            loop {
+                if options.partial_on_deadline && options.is_expired() {
+                    if let Some(stats) = options.stats {
+                        stats.truncated.store(true, std::sync::atomic::Ordering::Relaxed);
+                    }
+                    self.finish_traversal(&mut traversal).await;
+                    break;
+                }
+                let batch = match self.next_batch::<V>(index, source, options, &mut traversal).await? {
+                    Some(batch) => batch,
+                    None => break,
+                };
+                let hits = batch.get_hits()?;
+                #[cfg(feature = "tracing")]
+                tracing::debug!(batch = traversal.batches, hits = hits.len(), "batch fetched");
+                if let Some(stats) = options.stats {
+                    stats.documents.fetch_add(hits.len(), std::sync::atomic::Ordering::Relaxed);
+                }
+                for hit in hits {
+                    list.push(map(hit)?);
+                    if order_ties {
+                        keys.push((Self::hit_sort_value::<V>(hit, &traversal.sort)?, hit.find_json("\"_id\"")?.get_unescaped()?));
+                    }
+                }
+            }
+
+            // If result is reverse query data, reverse it back.
+            if traversal.reverse {
+                list.reverse();
+                keys.reverse();
+            }
+            if order_ties {
+                let truncated = options.partial_on_deadline && options.is_expired();
+                list = self.order_ties::<V, T, F>(index, from, size, truncated, options, &traversal, list, keys, map).await.map_err(|e| e.in_phase("tie-order"))?;
+            }
+
+            #[cfg(feature = "tracing")]
+            tracing::debug!(requests = traversal.requests, batches = traversal.batches, documents = list.len(), reverse = traversal.reverse, elapsed_ms = start.elapsed().as_millis() as u64, "search finished");
+            Ok((list, traversal.total))
+        }
+
+        /// Order the documents sharing a sort value by "_id" in both query directions, the order of a forward query sorted 
+        /// by the sort field and then by "_id", which can not be sorted on by default since elasticsearch 8.0. 
+        /// The `list` holds the documents of the window starting at `from` in the order of the sort field, with the neighbor 
+        /// documents fetched by the traversal on each side, and `keys` their sort values and ids. 
+        /// Each run of tied documents within the window is sorted by "_id". When a neighbor shares the sort value of the first 
+        /// or the last document, the window cuts that run, so the ids of the whole run are fetched to pick the documents 
+        /// of the window, and the ones the traversal did not return are fetched by id. 
+        #[allow(clippy::too_many_arguments)]
+        async fn order_ties<V, T, F>(&self, index: &str, from: i64, size: i64, truncated: bool, options: &SearchOptions<'_>, traversal: &Traversal, mut list: Vec<T>, mut keys: Vec<(V, String)>, map: &F) -> Result<Vec<T>, Error>
+        where V: SortValue, F: Fn(&EsJson) -> Result<T, Error> {
+            let lead = traversal.lead.min(list.len());
+            if list.len() <= lead {
+                return Ok(vec![]);
+            }
+            let count = list.len();
+            let trail = usize::from(traversal.trail > 0 && count as i64 == size + (traversal.lead + traversal.trail) as i64);
+            // a truncated search sends no more requests, the runs at the ends of its window keep the documents it found.
+            let start_cut = !truncated && lead > 0 && keys[0].0 == keys[1].0;
+            let end_cut = !truncated && trail > 0 && keys[count - 1].0 == keys[count - 2].0;
+            list.truncate(count - trail);
+            keys.truncate(count - trail);
+            list.drain(..lead);
+            keys.drain(..lead);
+
+            let mut ordered = Vec::with_capacity(list.len());
+            let mut items = list.into_iter().zip(keys).peekable();
+            let mut first = true;
+            while let Some((item, (value, id))) = items.next() {
+                let mut run = vec![(id, item)];
+                while let Some((item, (_, id))) = items.next_if(|(_, (next, _))| *next == value) {
+                    run.push((id, item));
+                }
+                let cut_start = first && start_cut;
+                let cut_end = items.peek().is_none() && end_cut;
+                first = false;
+                if cut_start || cut_end {
+                    run = self.cut_run::<V, T, F>(index, from, value, cut_start, cut_end, options, traversal, run, map).await?;
+                } else {
+                    run.sort_by(|a, b| a.0.cmp(&b.0));
+                }
+                ordered.extend(run.into_iter().map(|(_, item)| item));
+            }
+            Ok(ordered)
+        }
+
+        /// Pick the documents of a run of tied documents cut by the window, by the position of the window in the run 
+        /// ordered by "_id". The window starts within the run when `cut_start`, and ends within it when `cut_end`. 
+        /// When both, the run covers the whole window, and the documents of the run before the window are counted. 
+        #[allow(clippy::too_many_arguments)]
+        async fn cut_run<V, T, F>(&self, index: &str, from: i64, value: V, cut_start: bool, cut_end: bool, options: &SearchOptions<'_>, traversal: &Traversal, run: Vec<(String, T)>, map: &F) -> Result<Vec<(String, T)>, Error>
+        where V: SortValue, F: Fn(&EsJson) -> Result<T, Error> {
+            let run_query = Self::build_range_query(&traversal.query, &traversal.sort, value, value);
+            let mut ids = self.run_ids(index, &run_query, options).await?;
+            ids.sort();
+            let offset = match (cut_start, cut_end) {
+                (true, true) => {
+                    let cmp = if traversal.asc != traversal.reverse { "lt" } else { "gt" };
+                    let before = self.count_with(index, &Self::build_cmp_query(&traversal.query, &traversal.sort, cmp, value), options).await?;
+                    usize::try_from(from - before).unwrap_or(0)
+                }
+                (true, false) => ids.len().saturating_sub(run.len()),
+                _ => 0,
+            };
+            let selected = &ids[offset.min(ids.len())..(offset + run.len()).min(ids.len())];
+
+            let mut found = run.into_iter().collect::<std::collections::HashMap<String, T>>();
+            let missing = selected.iter().filter(|id| !found.contains_key(*id)).map(|id| json_string(id)).collect::<Vec<String>>();
+            let source = traversal.source.as_ref().and_then(Option::as_deref);
+            for chunk in missing.chunks(self.batch_size as usize) {
+                let ids_query = format!("{{\"bool\":{{\"must\":{},\"filter\":{{\"ids\":{{\"values\":[{}]}}}}}}}}", run_query, chunk.join(","));
+                let batch = self.query(index, &ids_query, source, &traversal.sort_clause, 0, chunk.len() as i64, None, false, options).await?;
+                for hit in batch.get_hits()? {
+                    found.insert(hit.find_json("\"_id\"")?.get_unescaped()?, map(hit)?);
+                }
+            }
+            // documents deleted since the traversal are left out.
+            Ok(selected.iter().filter_map(|id| found.remove(id).map(|item| (id.clone(), item))).collect())
+        }
+
+        /// Fetch the ids of all documents of the `query` with "search_after" over a point in time, 
+        /// the point in time of the options or one opened on the index and closed afterwards. 
+        async fn run_ids(&self, index: &str, query: &str, options: &SearchOptions<'_>) -> Result<Vec<String>, Error> {
+            let (mut pit, own_pit) = match options.pit {
+                Some(pit) => (String::from(pit), false),
+                None => (self.open_pit(index, SCAN_KEEP_ALIVE).await?, true),
+            };
+            let keep_alive = options.pit_keep_alive.or(if own_pit { Some(SCAN_KEEP_ALIVE) } else { None });
+            let result = async {
+                let mut ids = vec![];
+                let mut search_after = None;
+                loop {
+                    let current = pit.clone();
+                    let step_options = SearchOptions { pit: Some(&current), pit_keep_alive: keep_alive, ..*options };
+                    let batch = self.query("", query, Some("false"), "[\"_doc\"]", 0, self.batch_size, search_after.as_deref(), false, &step_options).await?;
+                    if let Some(pit_id) = batch.find_optional_json("\"pit_id\"")? {
+                        pit = pit_id.get_unescaped()?;
+                    }
+                    let hits = batch.get_hits()?;
+                    for hit in hits {
+                        ids.push(hit.find_json("\"_id\"")?.get_unescaped()?);
+                    }
+                    match hits.last() {
+                        Some(last) if hits.len() as i64 == self.batch_size => search_after = Some(EsJsonAnalyzer::to_json(last.find_json("\"sort\"")?)),
+                        _ => return Ok(ids),
+                    }
+                }
+            }.await;
+            if own_pit {
+                let closed = self.close_pit(&pit).await;
+                if let (Ok(_), Err(e)) = (&result, closed) {
+                    return Err(e);
+                }
+            }
+            result
+        }
+
+        /// Call the field capabilities API of elasticsearch, and check the sort field of the matched indices, 
+        /// its existence and numeric type when `preflight` is specified, and its consistent type when `check_sort_type` is specified. 
+        async fn check_field_caps(&self, index: &str, sort: &str, options: &SearchOptions<'_>) -> Result<(), Error> {
+            options.check_cancelled()?;
+            let url = format!("{}/_field_caps?fields={}", index, encode_url_component(sort));
+            let resp = match self.request(Method::Get, &url, None, options.headers).await {
+                Err(Error::Http { status: 404, .. }) if options.preflight => return Err(Error::Message(format!("index not found: {}", index))),
+                resp => resp?,
+            };
+            options.record_request(0, resp.len());
+            let json = self.parse_response(&resp)?;
+            if options.preflight && json.find_optional_json("\"indices\"")?.is_some_and(|indices| indices.get_array().is_ok_and(|i| i.is_empty())) {
+                return Err(Error::Message(format!("index not found: {}", index)));
+            }
+            let types = match json.find_json("\"fields\"")?.find_optional_json(&format!("\"{}\"", sort))? {
+                Some(types) => types.get_object()?,
+                None if options.preflight => return Err(Error::Message(format!("sort field '{}' is not mapped", sort))),
+                None => return Ok(()),
+            };
+            if options.preflight {
+                if let Some((field_type, _)) = types.iter().find(|(field_type, _)| !NUMERIC_TYPES.contains(&field_type.trim_matches('"'))) {
+                    return Err(Error::Message(format!("sort field '{}' is not numeric: {}", sort, field_type.trim_matches('"'))));
+                }
+            }
+            if options.check_sort_type && types.len() > 1 {
+                let mut conflicts = vec![];
+                for (field_type, caps) in types {
+                    let indices = match caps.find_optional_json("\"indices\"")? {
+                        Some(indices) => indices.get_array()?.iter().map(|i| i.get_unescaped()).collect::<Result<Vec<String>, Error>>()?,
+                        None => vec![],
+                    };
+                    conflicts.push(format!("{} [{}]", field_type.trim_matches('"'), indices.join(",")));
+                }
+                return Err(Error::Message(format!("sort field {} has conflicting types: {}.", sort, conflicts.join(", "))));
+            }
+            Ok(())
+        }
+
+        /// Validate parameters and compute the query, direction and from value of the first batch. 
+        /// Returns None if no documents can match, or a finished traversal carrying the total when from is beyond the total. 
+        /// With `order_ties`, the traversal also fetches the neighbor document on each side of the window, see `order_ties`. 
+        #[allow(clippy::too_many_arguments)]
+        async fn plan<V: SortValue>(&self, index: &str, query: &str, sort: &str, asc: bool, from: i64, size: i64, allow_reverse: bool, order_ties: bool, options: &SearchOptions<'_>) -> Result<Option<Traversal>, Error> {
+
+            // validate parameters
+            if index.is_empty() && options.pit.is_none() {
+                return Err(Error::InvalidParameter(String::from("index can not be empty.")));
+            }
+            if sort.is_empty() {
+                return Err(Error::InvalidParameter(String::from("sort can not be empty.")));
+            }
+            if from < 0 || size < 0 {
+                return Err(Error::InvalidParameter(String::from("from and size can not be negative.")));
+            }
+            if from.checked_add(size).is_none() {
+                return Err(Error::InvalidParameter(String::from("from/size out of range")));
+            }
+            if options.collapse.is_some() && (from > self.max_from || size > self.batch_size) {
+                return Err(Error::InvalidParameter(String::from("collapse can not be used with from greater than max_from or size greater than batch_size.")));
+            }
+            if self.max_documents.is_some_and(|max_documents| size > max_documents) {
+                return Err(Error::InvalidParameter(String::from("result size exceeds configured max_documents")));
+            }
+            if options.doc_type.is_some_and(|doc_type| doc_type.is_empty()) {
+                return Err(Error::InvalidParameter(String::from("doc_type can not be empty.")));
+            }
+            if options.doc_type.is_some() && options.pit.is_some() {
+                return Err(Error::InvalidParameter(String::from("doc_type can not be used with pit.")));
+            }
+            if options.sort_missing.is_some_and(|missing| missing != "_first" && missing != "_last" && missing.parse::<f64>().is_err()) {
+                return Err(Error::InvalidParameter(String::from("sort_missing must be \"_first\", \"_last\" or a number.")));
+            }
+            if options.terminate_after.is_some_and(|terminate_after| terminate_after < 1) {
+                return Err(Error::InvalidParameter(String::from("terminate_after must be positive.")));
+            }
+            if options.min_score.is_some() && from > self.max_from {
+                return Err(Error::InvalidParameter(String::from("min_score can not be used with from greater than max_from.")));
+            }
+            if size == 0 {
+                return Ok(None);
+            }
+            
+            let query = Self::normalize_query(query)?;
+            let exists_query;
+            let query = if options.skip_missing_sort {
+                exists_query = Self::build_exists_query(query, sort);
+                exists_query.as_str()
+            } else {
+                query
+            };
+            // Without a tie-breaker, documents sharing a sort value are ordered by "_id", one more document is fetched 
+            // on each side of the window to find out whether the window cuts a run of them.
+            let order_ties = order_ties && options.tie_break.is_none() && options.sort_missing.is_none() && options.collapse.is_none()
+                && options.terminate_after.is_none() && options.doc_type.is_none();
+            let lead = usize::from(order_ties && from > 0);
+            let trail = usize::from(order_ties && (from + size).checked_add(1).is_some());
+            let mut asc = asc;
+            let mut from = from - lead as i64;
+            let mut size = size + (lead + trail) as i64;
+
+            let mut reverse = false;
+            let mut requests = 0;
+            let mut total = None;
+            let mut new_query = String::from(query);
+            let mut new_from = from;
+            let mut start = None;
+            // point in time and cursor of the tied documents skipped with "search_after".
+            let (mut pit, mut own_pit, mut search_after) = (None, false, None);
+            if from > self.max_from {
+
+                let mut bounds = match self.cached_bounds::<V>(index, query, sort, options) {
+                    Some(bounds) => bounds,
+                    None => {
+                        let bounds = self.probe_bounds::<V>(index, query, sort, options, &mut requests).await?;
+                        self.cache_bounds(index, query, sort, options, &bounds);
+                        bounds
+                    }
+                };
+                total = bounds.total;
+                if total.is_some_and(|total| total == 0 || from > total) {
+                    return Ok(Some(Traversal { total, requests, ..Default::default() }));
+                }
+                let (sort_min, sort_max) = match (bounds.min, bounds.max) {
+                    (Some(sort_min), Some(sort_max)) => (sort_min, sort_max),
+                    _ => return Ok(None),
+                };
+
+                // When the queried data is near the end of the data set, reverse the query direction.
+                // Documents missing the sort field are placed last in both directions, so the reversed window is mirrored 
+                // within the documents that have a sort value, and the query is not reversed when the window reaches the missing ones.
+                let allow_reverse = allow_reverse && options.terminate_after.is_none() && options.sort_missing.is_none();
+                let mut reverse_total = total.filter(|total| allow_reverse && total.checked_sub(from).is_some_and(|rest| from > rest));
+                if let Some(total) = reverse_total.filter(|_| !options.skip_missing_sort) {
+                    let valued_total = match bounds.valued_total {
+                        Some(valued_total) => valued_total,
+                        None => {
+                            requests += 1;
+                            let exists_query = Self::build_exists_query(query, sort);
+                            let valued_total = self.count_with(index, &exists_query, options).await.map_err(|e| e.in_phase("count"))?;
+                            bounds.valued_total = Some(valued_total);
+                            self.cache_bounds(index, query, sort, options, &bounds);
+                            valued_total
+                        }
+                    };
+                    reverse_total = if valued_total == total || from + size <= valued_total { Some(valued_total) } else { None };
+                }
+                if let Some(reverse_total) = reverse_total {
+                    reverse = true;
+                    asc = !asc;
+                    (from, size) = Self::reverse_window(reverse_total, from, size)?;
+                    if size == 0 {
+                        return Ok(Some(Traversal { total, requests, ..Default::default() }));
+                    }
+                    new_from = from;
+                }
+
+                // When the from parameter is large, find a sort value that can exclude some of the from data, and reduce the from value.
+                if from > self.max_from {
+                    let new_start: V;
+                    let cmp = if asc { "gt" } else { "lt" };
+                    if asc {
+                        (new_start, new_from) = self.find_new_from(index, query, sort, sort_min, sort_max, from, options, &mut requests).await?;
+                    } else {
+                        (new_start, new_from) = self.find_new_from(index, query, sort, sort_max, sort_min, from, options, &mut requests).await?;
+                    }
+                    new_query = Self::build_cmp_query(query, sort, cmp, new_start);
+                    start = Some(new_start.to_string());
+
+                    // When many documents share the sort values at the target offset, the from value can not be reduced 
+                    // by sort value ranges, skip the tied documents with the tie-breaker field instead, 
+                    // or with "search_after" over a point in time without one, only a point in time of the options 
+                    // covers the planning requests above as well.
+                    if new_from < 0 || new_from > self.max_from {
+                        let (base_query, skip) = if new_from < 0 { (String::from(query), from) } else { (new_query, new_from) };
+                        match options.tie_break {
+                            Some(tie_break) => (new_query, new_from) = self.skip_ties::<V>(index, query, &base_query, sort, tie_break, asc, skip, options, &mut requests).await?,
+                            // mapping types predate point in time.
+                            None if options.doc_type.is_some() => return Err(Error::Message(format!("too many documents share the sort value {} of {} at offset {}, set tie_break to page through them.", new_start, sort, from))),
+                            None => {
+                                let (skip_pit, own, after) = self.skip_ties_after(index, &base_query, sort, asc, skip, options, &mut requests).await?;
+                                (pit, own_pit, search_after) = (Some(skip_pit), own, after);
+                                new_query = base_query;
+                                new_from = 0;
+                            }
+                        }
+                    }
+                }
+            }
+
+            Ok(Some(Traversal {
+                query: String::from(query),
+                sort: String::from(sort),
+                range_query: RangeQuery::new(query, sort),
+                sort_clause: String::new(),
+                source: None,
+                tie_break: options.tie_break.map(String::from),
+                dedup: options.dedup && options.tie_break.is_none(),
+                boundary_value: String::new(),
+                boundary_ids: vec![],
+                asc,
+                reverse,
+                next_query: new_query,
+                next_from: new_from,
+                remain_size: size,
+                start,
+                total,
+                pit,
+                own_pit,
+                search_after,
+                lead,
+                trail,
+                requests,
+                batches: 0,
+            }))
+        }
+
+        /// Mirror the window of `from` and `size` within `total` documents, returns the from and size of the same documents 
+        /// in the reversed order. The window is cut at the end of the documents, so the size is 0 when from is not less than total. 
+        /// The arithmetic is checked, so huge from and size values fail instead of overflowing. 
+        fn reverse_window(total: i64, from: i64, size: i64) -> Result<(i64, i64), Error> {
+            let out_of_range = || Error::InvalidParameter(String::from("from/size out of range"));
+            let reverse_from = total.checked_sub(from).and_then(|rest| rest.checked_sub(size)).ok_or_else(out_of_range)?;
+            let reverse_size = if reverse_from < 0 { size.checked_add(reverse_from).ok_or_else(out_of_range)? } else { size };
+            Ok((reverse_from.max(0), reverse_size.max(0)))
+        }
+
+        /// Find the total number of documents and the minimum and maximum sort values that meet query conditions. 
+        /// The `requests` is increased by the number of requests issued. 
+        async fn probe_bounds<V: SortValue>(&self, index: &str, query: &str, sort: &str, options: &SearchOptions<'_>, requests: &mut usize) -> Result<Bounds<V>, Error> {
+            // Find the minimum and maximum sort values concurrently, the minimum probe also returns the total number of documents 
+            // that meet query conditions.
+            let track_total_hits = options.total_hits == TotalHits::Eager;
+            let probe_source = Self::build_source(Some(&vec![sort]), None);
+            let (min_item, max_item) = tokio::try_join!(
+                async { self.query(index, query, probe_source.as_deref(), &Self::build_sort(sort, None, true, None), 0, 1, None, track_total_hits, options).await.map_err(|e| e.in_phase("probe-min")) },
+                async { self.query(index, query, probe_source.as_deref(), &Self::build_sort(sort, None, false, None), 0, 1, None, false, options).await.map_err(|e| e.in_phase("probe-max")) },
+            )?;
+            *requests += 2;
+            let total = match (options.total_hits, min_item.get_total()?) {
+                (TotalHits::Never, _) => None,
+                (_, Some(total)) => Some(total),
+                (TotalHits::Eager, None) => {
+                    *requests += 1;
+                    Some(self.count_with(index, query, options).await.map_err(|e| e.in_phase("count"))?)
+                }
+                (TotalHits::Lazy, None) => None,
+            };
+            let min = min_item.get_hits()?.last().map(|item| Self::source_sort_value(item, sort)).transpose()?;
+            let max = max_item.get_hits()?.last().map(|item| Self::source_sort_value(item, sort)).transpose()?;
+            Ok(Bounds { total, min, max, valued_total: None })
+        }
+
+        /// Key of the cached bounds of a search, everything that changes the result of the probes. 
+        /// The headers are part of it, as they may select another tenant or user of a proxy or of the security of the cluster. 
+        fn cache_key<V: SortValue>(index: &str, query: &str, sort: &str, options: &SearchOptions<'_>) -> String {
+            format!("{}\n{}\n{}\n{}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{}\n{:?}\n{:?}", V::NAME, index, query, sort, options.post_filter, options.pit, 
+                options.routing, options.preference, options.terminate_after, options.total_hits as u8, options.doc_type, options.headers)
+        }
+
+        /// Get the cached bounds of a search, None when caching is disabled, or the bounds are absent or expired. 
+        fn cached_bounds<V: SortValue>(&self, index: &str, query: &str, sort: &str, options: &SearchOptions<'_>) -> Option<Bounds<V>> {
+            self.cache_ttl?;
+            let key = Self::cache_key::<V>(index, query, sort, options);
+            let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+            let (bounds, expires) = cache.get(&key)?;
+            if std::time::Instant::now() >= *expires {
+                return None;
+            }
+            let min = match &bounds.min {
+                Some(min) => Some(V::parse(min)?),
+                None => None,
+            };
+            let max = match &bounds.max {
+                Some(max) => Some(V::parse(max)?),
+                None => None,
+            };
+            Some(Bounds { total: bounds.total, min, max, valued_total: bounds.valued_total })
+        }
+
+        /// Cache the bounds of a search for the cache ttl of the client, expired bounds are removed. 
+        fn cache_bounds<V: SortValue>(&self, index: &str, query: &str, sort: &str, options: &SearchOptions<'_>, bounds: &Bounds<V>) {
+            let ttl = match self.cache_ttl {
+                Some(ttl) => ttl,
+                None => return,
+            };
+            let key = Self::cache_key::<V>(index, query, sort, options);
+            let bounds = Bounds { total: bounds.total, min: bounds.min.map(|v| v.to_string()), max: bounds.max.map(|v| v.to_string()), valued_total: bounds.valued_total };
+            let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+            let now = std::time::Instant::now();
+            cache.retain(|_, (_, expires)| *expires > now);
+            cache.insert(key, (bounds, now + ttl));
+        }
+
+        /// Fetch the next batch of the traversal and move the cursor after its last document. 
+        /// Returns None when the traversal is finished. 
+        async fn next_batch<V: SortValue>(&self, index: &str, source: Option<&Vec<&str>>, options: &SearchOptions<'_>, traversal: &mut Traversal) -> Result<Option<EsJson>, Error> {
+            if traversal.remain_size <= 0 {
+                self.finish_traversal(traversal).await;
+                return Ok(None);
+            }
+            // keep the from and size of the batch within the result window of the index.
+            let retrieve_size = traversal.remain_size.min(self.batch_size).min(self.max_result_window - traversal.next_from);
+            // the sort clause and the source filter are the same for every batch, unless a source resolver picks the fields.
+            if traversal.sort_clause.is_empty() {
+                traversal.sort_clause = Self::build_sort(&traversal.sort, traversal.tie_break.as_deref(), traversal.asc, options.sort_missing);
+            }
+            let resolved = options.source_resolver.map(|resolver| resolver(traversal.batches));
+            if resolved.as_ref().is_some_and(|source| source.iter().any(|field| field.contains(['\"', '\\']))) {
+                return Err(Error::InvalidParameter(String::from("source_resolver is not valid JSON")));
+            }
+            if resolved.is_some() || traversal.source.is_none() {
+                let source = resolved.as_ref().or(source);
+                traversal.source = Some(match Self::build_source(source, options.source_excludes) {
+                    None if options.docvalue_fields.is_some() || options.fields.is_some() => Some(String::from("false")),
+                    source => source,
+                });
+            }
+            let source = traversal.source.as_ref().and_then(Option::as_deref);
+            let pit = traversal.pit.clone();
+            let keep_alive = options.pit_keep_alive.or(if traversal.own_pit { Some(SCAN_KEEP_ALIVE) } else { None });
+            let batch_options = SearchOptions { pit: pit.as_deref().or(options.pit), pit_keep_alive: keep_alive, ..*options };
+            let batch = match self.query(index, &traversal.next_query, source, &traversal.sort_clause, traversal.next_from, retrieve_size, traversal.search_after.as_deref(), false, &batch_options).await {
+                Ok(batch) => batch,
+                Err(e) => {
+                    self.finish_traversal(traversal).await;
+                    return Err(e.in_phase(&format!("batch #{}", traversal.batches + 1)));
+                }
+            };
+            traversal.requests += 1;
+            if batch_options.pit.is_some() {
+                if let Some(pit_id) = batch.find_optional_json("\"pit_id\"")? {
+                    traversal.pit = Some(pit_id.get_unescaped()?);
+                }
+            }
+            let hits = batch.get_hits()?;
+            if hits.is_empty() {
+                traversal.remain_size = 0;
+                self.finish_traversal(traversal).await;
+                return Ok(None);
+            }
+            traversal.batches += 1;
+            traversal.remain_size -= hits.len() as i64;
+            if traversal.remain_size > 0 {
+                let last_item = hits.last().unwrap();
+                if traversal.search_after.is_some() {
+                    traversal.search_after = Some(EsJsonAnalyzer::to_json(last_item.find_json("\"sort\"")?));
+                    return Ok(Some(batch));
+                }
+                let last_sort_values = last_item.find_json("\"sort\"")?.get_array()?;
+                let last_sort = Self::hit_sort_value::<V>(last_item, &traversal.sort)?;
+                let cmp = if traversal.asc { "gt" } else { "lt" };
+                traversal.next_query = match (&traversal.tie_break, last_sort_values.get(1)) {
+                    (Some(tie_break), Some(tie_value)) => {
+                        let tie_value = EsJsonAnalyzer::to_json(tie_value);
+                        Self::build_tie_break_query(&traversal.query, &traversal.sort, cmp, last_sort, tie_break, &tie_value)
+                    }
+                    (Some(_), None) => return Err(Error::invalid_json()),
+                    (None, _) if traversal.dedup => {
+                        // collect the ids of the trailing documents sharing the boundary sort value.
+                        let boundary_value = last_sort.to_string();
+                        if traversal.boundary_value != boundary_value {
+                            traversal.boundary_value = boundary_value;
+                            traversal.boundary_ids.clear();
+                        }
+                        for hit in hits.iter().rev() {
+                            if Self::hit_sort_value::<V>(hit, &traversal.sort)? != last_sort {
+                                break;
+                            }
+                            traversal.boundary_ids.push(EsJsonAnalyzer::to_json(hit.find_json("\"_id\"")?));
+                        }
+                        let cmp = if traversal.asc { "gte" } else { "lte" };
+                        Self::build_dedup_query(&traversal.query, &traversal.sort, cmp, last_sort, &traversal.boundary_ids)
+                    }
+                    (None, _) => traversal.range_query.cmp(cmp, last_sort),
+                };
+                traversal.next_from = 0;
+            }
+            Ok(Some(batch))
+        }
+
+        /// Call elasticsearch's searchAPI to get the documents that meet the conditions. 
+        /// The `source` is a json formatted source filter built by `build_source`. 
+        /// The `sort` is a json formatted sort clause built by `build_sort`. 
+        /// If `search_after` is specified, it is placed in the "search_after" field of the request body. 
+        /// If `track_total_hits` is true, the response contains the accurate total number of documents that meet query conditions. 
+        /// If a point in time is specified in `options`, the request targets "_search" without the index in the path. 
+        #[allow(clippy::too_many_arguments)]
+        #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(index = %index, from = from, size = size)))]
+        async fn query(&self, index: &str, query: &str, source: Option<&str>, sort: &str, from: i64, size: i64, search_after: Option<&str>, track_total_hits: bool, options: &SearchOptions<'_>) -> Result<EsJson, Error> {
+            let resp = self.query_raw(index, query, source, sort, from, size, search_after, track_total_hits, options).await?;
+            let mut json = self.parse_response(&resp)?;
+            if options.filter_path {
+                // the filtered response omits "hits.hits" when no documents match.
+                json.fill_hits();
+            }
+            self.check_search_response(&json)?;
+
+            Ok(json)
+        }
+
+        /// Check the shard failures and the "timed_out" field of a search response, unless the client allows them. 
+        fn check_search_response(&self, json: &EsJson) -> Result<(), Error> {
+            if !self.allow_shard_failures {
+                json.check_shards()?;
+            }
+            if !self.allow_timed_out && json.is_timed_out()? {
+                return Err(Error::Message(String::from("search timed out, results may be incomplete")));
+            }
+            Ok(())
+        }
+
+        /// Build the request body of the searchAPI and send it, same as `query`, but returns the response as it is. 
+        #[allow(clippy::too_many_arguments)]
+        async fn query_raw(&self, index: &str, query: &str, source: Option<&str>, sort: &str, from: i64, size: i64, search_after: Option<&str>, track_total_hits: bool, options: &SearchOptions<'_>) -> Result<String, Error> {
+            options.check_cancelled()?;
+
+            let url = match options.pit {
+                Some(_) => String::from("_search"),
+                None => options.index_path(index, "_search"),
+            };
+            let url = options.build_url(url);
+            let url = match options.filter_path {
+                true => format!("{}{}filter_path={}", url, if url.contains('?') { "&" } else { "?" }, SEARCH_FILTER_PATH),
+                false => url,
+            };
+
+            // the body is written into one buffer sized for the query, so a large query is copied once per request.
+            let mut query_builder = String::with_capacity(query.len() + sort.len() + source.map_or(0, str::len) + options.post_filter.map_or(0, str::len) + 256);
+            query_builder.push('{');
+            let _ = write!(query_builder, "\"query\":{},", query);
+            let _ = write!(query_builder, "\"sort\":{},", sort);
+            if let Some(source) = source {
+                let _ = write!(query_builder, "\"_source\": {},", source);
+            }
+            if let Some(search_after) = search_after {
+                let _ = write!(query_builder, "\"search_after\":{},", search_after);
+            }
+            if let Some(docvalue_fields) = options.docvalue_fields {
+                query_builder.push_str("\"docvalue_fields\":[");
+                for (i, field) in docvalue_fields.iter().enumerate() {
+                    let _ = write!(query_builder, "{}\"{}\"", if i > 0 { "," } else { "" }, field);
+                }
+                query_builder.push_str("],");
+            }
+            if let Some(fields) = options.fields {
+                query_builder.push_str("\"fields\":[");
+                for (i, field) in fields.iter().enumerate() {
+                    let _ = write!(query_builder, "{}\"{}\"", if i > 0 { "," } else { "" }, field);
+                }
+                query_builder.push_str("],");
+            }
+            if let Some(collapse) = options.collapse {
+                let _ = write!(query_builder, "\"collapse\":{{\"field\":\"{}\"}},", collapse);
+            }
+            if let Some(min_score) = options.min_score {
+                let _ = write!(query_builder, "\"min_score\":{},", min_score);
+            }
+            if let Some(post_filter) = options.post_filter {
+                let _ = write!(query_builder, "\"post_filter\":{},", post_filter);
+            }
+            if let Some(terminate_after) = options.terminate_after {
+                let _ = write!(query_builder, "\"terminate_after\":{},", terminate_after);
+            }
+            if options.seq_no_primary_term {
+                query_builder.push_str("\"seq_no_primary_term\":true,");
+            }
+            if track_total_hits {
+                query_builder.push_str("\"track_total_hits\":true,");
+            }
+            if let Some(pit) = options.pit {
+                query_builder.push_str(&Self::build_pit(pit, options.pit_keep_alive));
+                query_builder.push(',');
+            }
+            let _ = write!(query_builder, "\"from\":{},", from);
+            let _ = write!(query_builder, "\"size\":{} }}", size);
+
+            let body = query_builder;
+            let resp = self.post(&url, &body, options.headers).await?;
+            options.record_request(body.len(), resp.len());
+            Ok(resp)
+        }
+
+        /// Use binary search to find new query parameters with the same result as the original query but with a smaller from value. 
+        /// The `requests` is increased by the number of count requests issued. 
+        #[allow(clippy::too_many_arguments)]
+        #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(index = %index, from = from)))]
+        async fn find_new_from<V: SortValue>(&self, index: &str, query: &str, sort: &str, sort_start: V, sort_end: V, from: i64, options: &SearchOptions<'_>, requests: &mut usize) -> Result<(V, i64), Error> {
+            let range_query = RangeQuery::new(query, sort);
+            let mut new_start = sort_start;
+            let mut new_end = sort_end;
+            let mut new_from: i64;
+            let mut start_from = None;
+            loop {
+                let (sort_min, sort_max) = if new_start < new_end { (new_start, new_end) } else { (new_end, new_start) };
+                let sort_mid = match V::midpoint(sort_min, sort_max) {
+                    Some(sort_mid) => sort_mid,
+                    None => {
+                        // No value lies between the bounds, the documents after new_start begin at new_end. 
+                        // The from value may still be large, or negative when the documents at sort_start exceed it, 
+                        // if many documents share these sort values.
+                        let start_from = match start_from {
+                            Some(start_from) => start_from,
+                            None => {
+                                let start_query = range_query.range(sort_start, sort_start);
+                                *requests += 1;
+                                from - self.count_with(index, &start_query, options).await.map_err(|e| e.in_phase("binary-search"))?
+                            }
+                        };
+                        return Ok((new_start, start_from));
+                    }
+                };
+
+                let mid_query = if sort_start < sort_end {
+                    range_query.range(sort_start, sort_mid)
+                } else {
+                    range_query.range(sort_mid, sort_start)
+                };
+                let mid_count = self.count_with(index, &mid_query, options).await.map_err(|e| e.in_phase("binary-search"))?;
+                *requests += 1;
+                new_from = from - mid_count;
+                #[cfg(feature = "tracing")]
+                tracing::trace!(sort_mid = %sort_mid, mid_count, new_from, "binary search step");
+
+                if new_from < 0 {
+                    new_end = sort_mid;
+                } else {
+                    new_start = sort_mid;
+                    start_from = Some(new_from);
+                    if new_from <= self.max_from {
+                        break;
+                    }
+                }
+            }
+
+            Ok((new_start, new_from))
+        }
+
+        /// Skip documents of the `base_query` in the order of the sort field and the tie-breaker field, 
+        /// moving a tuple cursor by max_from documents per request, until the remaining from value is small enough. 
+        /// Returns the query starting after the cursor and the remaining from value. 
+        /// The `requests` is increased by the number of requests issued. 
+        #[allow(clippy::too_many_arguments)]
+        async fn skip_ties<V: SortValue>(&self, index: &str, query: &str, base_query: &str, sort: &str, tie_break: &str, asc: bool, skip: i64, options: &SearchOptions<'_>, requests: &mut usize) -> Result<(String, i64), Error> {
+            let cmp = if asc { "gt" } else { "lt" };
+            let sort_clause = Self::build_sort(sort, Some(tie_break), asc, None);
+            let mut next_query = String::from(base_query);
+            let mut remain = skip;
+            while remain > self.max_from {
+                let resp = self.query(index, &next_query, Some("false"), &sort_clause, self.max_from, 1, None, false, options).await.map_err(|e| e.in_phase("skip-ties"))?;
+                *requests += 1;
+                let hit = match resp.get_hits()?.last() {
+                    Some(hit) => hit,
+                    // no more than max_from documents are left, all of them are skipped.
+                    None => return Ok((next_query, self.max_from)),
+                };
+                let value = Self::hit_sort_value::<V>(hit, sort)?;
+                let tie_value = match hit.find_json("\"sort\"")?.get_array()?.get(1) {
+                    Some(tie_value) => EsJsonAnalyzer::to_json(tie_value),
+                    None => return Err(Error::invalid_json()),
+                };
+                next_query = Self::build_tie_break_query(query, sort, cmp, value, tie_break, &tie_value);
+                remain -= self.max_from + 1;
+            }
+            Ok((next_query, remain))
+        }
+
+        /// Skip documents of the `base_query` in the order of the sort field when no tie-breaker field is specified, 
+        /// paging with "search_after" over a point in time, whose implicit "_shard_doc" tie-breaker orders the documents 
+        /// sharing a sort value. The point in time of the options is used, or one is opened on the index. 
+        /// An opened point in time freezes the index only from here, after the planning requests of the live index. 
+        /// Returns the point in time, whether it was opened here and must be closed, and the sort values of the last skipped document. 
+        /// The `requests` is increased by the number of requests issued. 
+        #[allow(clippy::too_many_arguments)]
+        async fn skip_ties_after(&self, index: &str, base_query: &str, sort: &str, asc: bool, skip: i64, options: &SearchOptions<'_>, requests: &mut usize) -> Result<(String, bool, Option<String>), Error> {
+            let (mut pit, own_pit) = match options.pit {
+                Some(pit) => (String::from(pit), false),
+                None => {
+                    *requests += 1;
+                    (self.open_pit(index, SCAN_KEEP_ALIVE).await.map_err(|e| e.in_phase("skip-ties"))?, true)
+                }
+            };
+            let sort_clause = Self::build_sort(sort, None, asc, None);
+            let keep_alive = options.pit_keep_alive.or(if own_pit { Some(SCAN_KEEP_ALIVE) } else { None });
+            let mut search_after = None;
+            let mut remain = skip;
+            while remain > 0 {
+                let step = remain.min(self.batch_size).min(self.max_result_window);
+                let current = pit.clone();
+                let step_options = SearchOptions { pit: Some(&current), pit_keep_alive: keep_alive, ..*options };
+                let resp = match self.query("", base_query, Some("false"), &sort_clause, 0, step, search_after.as_deref(), false, &step_options).await {
+                    Ok(resp) => resp,
+                    Err(e) => {
+                        if own_pit {
+                            let _ = self.close_pit(&pit).await;
+                        }
+                        return Err(e.in_phase("skip-ties"));
+                    }
+                };
+                *requests += 1;
+                if let Some(pit_id) = resp.find_optional_json("\"pit_id\"")? {
+                    pit = pit_id.get_unescaped()?;
+                }
+                let hits = resp.get_hits()?;
+                if let Some(last) = hits.last() {
+                    search_after = Some(EsJsonAnalyzer::to_json(last.find_json("\"sort\"")?));
+                }
+                // fewer documents are left than skipped, all of them are skipped.
+                if (hits.len() as i64) < step {
+                    break;
+                }
+                remain -= step;
+            }
+            Ok((pit, own_pit, search_after))
+        }
+
+        /// Close the point in time opened by `skip_ties_after` for the traversal. 
+        /// A failure is ignored, the point in time then expires after its keep alive time. 
+        async fn finish_traversal(&self, traversal: &mut Traversal) {
+            if traversal.own_pit {
+                traversal.own_pit = false;
+                if let Some(pit) = &traversal.pit {
+                    let _ = self.close_pit(pit).await;
+                }
+            }
+        }
+
+        /// Normalize an empty query to match all documents, and check that the query is well-formed json without a sort clause 
+        /// before sending it. 
+        fn normalize_query(query: &str) -> Result<&str, Error> {
+            if query.is_empty() {
+                return Ok("{\"match_all\":{}}");
+            }
+            let json = EsJsonAnalyzer::from_json(query).map_err(|_| Error::InvalidParameter(String::from("query is not valid JSON")))?;
+            // the client owns the sort clause of its requests, a sort embedded in the query would conflict with it.
+            if json.find_optional_json("\"sort\"").is_ok_and(|sort| sort.is_some()) {
+                return Err(Error::InvalidParameter(String::from("query can not contain a sort clause, use the sort parameter instead.")));
+            }
+            Ok(query)
+        }
+
+        /// Parse the value of the unique number sort field. 
+        fn parse_sort_value<V: SortValue>(value: &str, sort: &str) -> Result<V, Error> {
+            match V::parse(value) {
+                Some(value) => Ok(value),
+                None => Err(Error::Message(format!("sort field {} has non-{} value {}.", sort, V::NAME, value))),
+            }
+        }
+
+        /// Get the sort value of a hit from its "sort" field. 
+        fn hit_sort_value<V: SortValue>(hit: &EsJson, sort: &str) -> Result<V, Error> {
+            match hit.find_json("\"sort\"")?.get_array()?.first() {
+                Some(EsJson::Null) => Err(Self::missing_sort_error(hit, sort)),
+                Some(value) => Self::parse_sort_value(&EsJsonAnalyzer::to_json(value), sort),
+                None => Err(Error::invalid_json()),
+            }
+        }
+
+        /// Get the sort value of a hit from its "_source" field. 
+        fn source_sort_value<V: SortValue>(hit: &EsJson, sort: &str) -> Result<V, Error> {
+            match hit.find_optional_json("\"_source\"")?.map(|source| source.find_path(sort)) {
+                Some(Ok(value @ (EsJson::Number(_) | EsJson::String(_)))) => Self::parse_sort_value(&EsJsonAnalyzer::to_json(value), sort),
+                _ => Err(Self::missing_sort_error(hit, sort)),
+            }
+        }
+
+        fn missing_sort_error(hit: &EsJson, sort: &str) -> Error {
+            let id = hit.find_json("\"_id\"").and_then(|v| v.get_unescaped()).unwrap_or_default();
+            Error::Message(format!("document {} is missing sort field {}.", id, sort))
+        }
+
+        /// Build the source filter, an array of includes, or an object when excludes are specified. 
+        fn build_source(includes: Option<&Vec<&str>>, excludes: Option<&Vec<&str>>) -> Option<String> {
+            let to_array = |fields: &Vec<&str>| format!("[{}]", fields.iter().map(|s|format!("\"{}\"", s)).collect::<Vec<String>>().join(","));
+            match (includes, excludes) {
+                (Some(includes), Some(excludes)) => Some(format!("{{\"includes\":{},\"excludes\":{}}}", to_array(includes), to_array(excludes))),
+                (None, Some(excludes)) => Some(format!("{{\"excludes\":{}}}", to_array(excludes))),
+                (Some(includes), None) => Some(to_array(includes)),
+                (None, None) => None,
+            }
+        }
+
+        /// Build the sort clause of the unique number field, followed by the tie-breaker field if specified. 
+        /// The long form with the missing value is only written when `missing` is specified. 
+        fn build_sort(sort: &str, tie_break: Option<&str>, asc: bool, missing: Option<&str>) -> String {
+            let order = if asc { "asc" } else { "desc" };
+            let primary = match missing {
+                Some(missing) if missing.starts_with('_') => format!("{{\"{}\":{{\"order\":\"{}\",\"missing\":\"{}\"}}}}", sort, order, missing),
+                Some(missing) => format!("{{\"{}\":{{\"order\":\"{}\",\"missing\":{}}}}}", sort, order, missing),
+                None => format!("{{\"{}\":\"{}\"}}", sort, order),
+            };
+            match tie_break {
+                Some(tie_break) => format!("[{},{{\"{}\":\"{}\"}}]", primary, tie_break, order),
+                None => primary,
+            }
+        }
+
+        /// Add a restriction to the original query that the sort field exists. 
+        fn build_exists_query(query: &str, sort: &str) -> String {
+            format!("{{\"bool\":{{\"must\":{},\"filter\":{{\"exists\":{{\"field\":\"{}\"}}}}}}}}", query, sort)
+        }
+
+        /// Add range restrictions to the original query, see `RangeQuery` to build several of the same query. 
+        fn build_range_query<V: SortValue>(query: &str, sort: &str, start: V, end: V) -> String {
+            RangeQuery::new(query, sort).range(start, end)
+        }
+
+        /// Add range restrictions to the original query, see `RangeQuery` to build several of the same query. 
+        fn build_cmp_query<V: SortValue>(query: &str, sort: &str, cmp: &str, value: V) -> String {
+            RangeQuery::new(query, sort).cmp(cmp, value)
+        }
+
+        /// Add tuple comparison restrictions to the original query, 
+        /// the sort value is compared first, and the tie-breaker value is compared when the sort value is equal. 
+        fn build_tie_break_query<V: SortValue>(query: &str, sort: &str, cmp: &str, value: V, tie_break: &str, tie_value: &str) -> String {
+            let mut json = String::with_capacity(query.len() + 2 * sort.len() + tie_break.len() + tie_value.len() + 256);
+            let _ = write!(json, "{{\"bool\":{{\"must\":{},\"filter\":{{\"bool\":{{\"should\":[", query);
+            let _ = write!(json, "{{\"range\":{{\"{}\":{{\"{}\":{}}}}}}},", sort, cmp, value);
+            let _ = write!(json, "{{\"bool\":{{\"filter\":[{{\"term\":{{\"{}\":{}}}}},", sort, value);
+            let _ = write!(json, "{{\"range\":{{\"{}\":{{\"{}\":{}}}}}}}]}}}}", tie_break, cmp, tie_value);
+            json.push_str("],\"minimum_should_match\":1}}}}");
+            json
+        }
+
+        /// Add inclusive range restrictions to the original query, and exclude the documents with the specified ids. 
+        /// The `ids` are json formatted strings. 
+        fn build_dedup_query<V: SortValue>(query: &str, sort: &str, cmp: &str, value: V, ids: &[String]) -> String {
+            let mut json = String::with_capacity(query.len() + sort.len() + ids.iter().map(|id| id.len() + 1).sum::<usize>() + 192);
+            let _ = write!(json, "{{\"bool\":{{\"must\":{},\"filter\":{{\"range\":{{\"{}\":{{\"{}\":{}}}}}}},\"must_not\":{{\"ids\":{{\"values\":[", query, sort, cmp, value);
+            for (i, id) in ids.iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+                json.push_str(id);
+            }
+            json.push_str("]}}}}");
+            json
+        }
+
+        /// Build the "pit" field of the request body. 
+        fn build_pit(pit: &str, keep_alive: Option<&str>) -> String {
+            match keep_alive {
+                Some(keep_alive) => format!("\"pit\":{{\"id\":{},\"keep_alive\":{}}}", json_string(pit), json_string(keep_alive)),
+                None => format!("\"pit\":{{\"id\":{}}}", json_string(pit)),
+            }
+        }
+
+        /// Parse a response of elasticsearch, with the nesting depth limit of the client. 
+        fn parse_response(&self, resp: &str) -> Result<EsJson, Error> {
+            EsJsonAnalyzer::from_json_with_depth(resp, self.max_json_depth)
+        }
+
+        /// Call elasticsearch low level rest client, post json to elasticsearch cluster. 
+        async fn post(&self, url: &str, body: &str, headers: Option<&HeaderMap>) -> Result<String, Error> {
+            self.request(Method::Post, url, Some(body), headers).await
+        }
+
+        /// Call elasticsearch low level rest client, send a request with the specified method to elasticsearch cluster. 
+        /// If the timeout setting is specified, each attempt fails when the response is not completely received in time. 
+        /// If the retry setting is specified, throttled requests are retried with exponential backoff. 
+        /// If the server error retry setting is specified, transient server errors are retried the same way with their own count. 
+        /// Transport errors are sent again right away to the next node added by `ClientBuilder::add_node`, once per node. 
+        /// The `headers` are merged into the default headers of the client, replacing the default values of the same names. 
+        #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(method = ?method, url = %url)))]
+        async fn request(&self, method: Method, url: &str, body: Option<&str>, headers: Option<&HeaderMap>) -> Result<String, Error> {
+            let mut request_headers = self.headers.clone();
+            if let Some(headers) = headers {
+                request_headers.extend(headers.clone());
+            }
+            let mut throttle_attempt = 0;
+            let mut server_error_attempt = 0;
+            let mut node_attempt = 1;
+            loop {
+                #[cfg(feature = "tracing")]
+                let start = std::time::Instant::now();
+                let sent = match self.timeout {
+                    Some(timeout) => match tokio::time::timeout(timeout, self.send_once(method, url, body, &request_headers)).await {
+                        Ok(resp) => resp,
+                        Err(_) => return Err(Error::Timeout(timeout)),
+                    },
+                    None => self.send_once(method, url, body, &request_headers).await,
+                };
+                let (status, resp) = match sent {
+                    Err(Error::Transport(_)) if node_attempt < self.nodes => {
+                        node_attempt += 1;
+                        continue;
+                    }
+                    sent => sent?,
+                };
+                #[cfg(feature = "tracing")]
+                tracing::trace!(status, retries = throttle_attempt + server_error_attempt, elapsed_ms = start.elapsed().as_millis() as u64, "request sent");
+                if status == 200 {
+                    return Ok(resp);
+                }
+
+                let (retry, attempt) = match (&self.retry, &self.server_error_retry) {
+                    (Some(retry), _) if status == 429 || (status == 503 && retry.retry_unavailable) => (retry, &mut throttle_attempt),
+                    (_, Some(retry)) if status == 502 || status == 503 || status == 504 => (retry, &mut server_error_attempt),
+                    _ if status == 404 && resp.contains("search_context_missing_exception") => return Err(Error::PitExpired(resp)),
+                    _ => return Err(Error::Http { status, body: resp }),
+                };
+                if *attempt >= retry.max_retries {
+                    return Err(Error::Http { status, body: resp });
+                }
+                tokio::time::sleep(retry.backoff(*attempt)).await;
+                *attempt += 1;
+            }
+        }
+
+        /// Send a single request and read the response status and body. 
+        async fn send_once(&self, method: Method, url: &str, body: Option<&str>, headers: &HeaderMap) -> Result<(u16, String), Error> {
+            #[allow(unused_mut)]
+            let mut headers = headers.clone();
+            let body = body.map(str::as_bytes);
+
+            #[cfg(feature = "gzip")]
+            let compressed = match self.compression {
+                true => {
+                    use elasticsearch::http::headers::{HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING};
+                    headers.insert(ACCEPT_ENCODING, HeaderValue::from_static("gzip"));
+                    if body.is_some() {
+                        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+                    }
+                    body.map(gzip::compress).transpose()?
+                }
+                false => None,
+            };
+            #[cfg(feature = "gzip")]
+            let body = compressed.as_deref().or(body);
+
+            let resp = self.transport
+                .send(
+                    method,
+                    url,
+                    headers,
+                    Option::<&str>::None,
+                    body,
+                    None,
+                ).await;
+
+            match resp {
+                Ok(resp) => {
+                    let status = resp.status_code().as_u16();
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(request_bytes = body.map_or(0, <[u8]>::len), response_bytes = ?resp.content_length(), "response received");
+                    if let (Some(max), Some(length)) = (self.max_response_bytes, resp.content_length()) {
+                        if length > max {
+                            return Err(Error::Message(String::from("response exceeds max_response_bytes")));
+                        }
+                    }
+                    #[cfg(feature = "gzip")]
+                    let gzipped = resp.headers().get(elasticsearch::http::headers::CONTENT_ENCODING).is_some_and(|v| v == "gzip");
+                    let bytes = resp.bytes().await.map_err(|e| Error::Transport(format!("{}", e)))?.to_vec();
+                    #[cfg(feature = "gzip")]
+                    let bytes = if gzipped { gzip::decompress(&bytes)? } else { bytes };
+                    if self.max_response_bytes.is_some_and(|max| bytes.len() as u64 > max) {
+                        return Err(Error::Message(String::from("response exceeds max_response_bytes")));
+                    }
+                    let body = String::from_utf8(bytes).unwrap_or_default();
+                    Ok((status, body))
+                }
+                Err(e) => {
+                    Err(Error::Transport(format!("{}", e)))
+                }
+            }
+        }
+    }
+
+    /// Scroll context opened by `Client::scroll`. 
+    /// If it is dropped while the scroll id is still set, e.g. when the scan future is cancelled, 
+    /// the context is cleared by a background task on the current tokio runtime instead of waiting for the scroll timeout. 
+    struct ScrollGuard {
+        transport: Transport,
+        headers: HeaderMap,
+        scroll_id: Option<String>,
+    }
+
+    impl ScrollGuard {
+        fn clear_body(scroll_id: &str) -> String {
+            format!("{{\"scroll_id\":[{}]}}", json_string(scroll_id))
+        }
+    }
+
+    impl Drop for ScrollGuard {
+        fn drop(&mut self) {
+            let scroll_id = match self.scroll_id.take() {
+                Some(scroll_id) => scroll_id,
+                None => return,
+            };
+            if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                let transport = self.transport.clone();
+                let headers = self.headers.clone();
+                handle.spawn(async move {
+                    let body = ScrollGuard::clear_body(&scroll_id);
+                    let _ = transport.send(Method::Delete, "_search/scroll", headers, Option::<&str>::None, Some(body), None).await;
+                });
+            }
+        }
+    }
+
+    /// Pages of a scan created by `Client::paginate`. 
+    pub struct PageIterator<'a> {
+        client: &'a Client,
+        index: &'a str,
+        query: &'a str,
+        source: Option<&'a Vec<&'a str>>,
+        sort: &'a str,
+        asc: bool,
+        page_size: i64,
+        cursor: Option<Cursor>,
+        done: bool,
+    }
+
+    impl PageIterator<'_> {
+
+        /// Fetch the next page, one search per call. 
+        /// 
+        /// # Return
+        /// The documents of the next page, None when all the documents have been returned. 
+        /// When a request fails, the error is returned and the same page is fetched again by the next call. 
+        pub async fn next_page(&mut self) -> Result<Option<Vec<String>>, Error> {
+            if self.done {
+                return Ok(None);
+            }
+            let (docs, cursor) = self.client.search_cursor(self.index, self.query, self.source, self.sort, self.asc, self.cursor.as_ref(), self.page_size).await?;
+            if (docs.len() as i64) < self.page_size || cursor.is_none() {
+                self.done = true;
+            }
+            if docs.is_empty() {
+                return Ok(None);
+            }
+            self.cursor = cursor;
+            Ok(Some(docs))
+        }
+
+        /// The cursor after the last document returned, None before the first page. 
+        /// Pass it to `Client::search_cursor` to continue the scan later. 
+        pub fn cursor(&self) -> Option<&Cursor> {
+            self.cursor.as_ref()
+        }
+    }
+
+    /// Position of a scan returned by `Client::search_cursor`, the sort value of the last document and the sort order. 
+    /// Convert it to a string with `to_string` to store it, and parse it back with `parse`. 
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct Cursor {
+        last_sort: i64,
+        asc: bool,
+    }
+
+    impl std::fmt::Display for Cursor {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}:{}", if self.asc { "asc" } else { "desc" }, self.last_sort)
+        }
+    }
+
+    /// Position of a scan over a point in time returned by `Client::search_resume`, with everything needed to continue it: 
+    /// the point in time, the query, the sort field and the cursor. 
+    /// Convert it to a compact url-safe base64 string with `to_string` to store it, and parse it back with `parse`. 
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # fn main() -> Result<(), es_deep_pager::deep_page_client::Error> {
+    /// use es_deep_pager::deep_page_client::ResumeToken;
+    /// 
+    /// let token = ResumeToken::new("pit-id", "10m", "{\"term\":{\"tag\":\"a\"}}", "id", true);
+    /// let stored = token.to_string();
+    /// assert!(stored.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+    /// assert_eq!(stored.parse::<ResumeToken>()?, token);
+    /// assert!("not a token".parse::<ResumeToken>().is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct ResumeToken {
+        pit: String,
+        keep_alive: String,
+        query: String,
+        sort: String,
+        asc: bool,
+        cursor: Option<Cursor>,
+    }
+
+    impl ResumeToken {
+
+        /// Create a token to start a scan from the first document. 
+        /// 
+        /// # Parameters
+        /// 
+        /// * `pit`: 
+        ///   The id of the point in time returned by `Client::open_pit`. 
+        /// 
+        /// * `keep_alive`: 
+        ///   Keep alive time of the point in time, e.g. "10m", extended by each request of the scan. 
+        /// 
+        /// * `query`, `sort`, `asc`: 
+        ///   Same as `Client::search`. 
+        pub fn new(pit: &str, keep_alive: &str, query: &str, sort: &str, asc: bool) -> ResumeToken {
+            ResumeToken {
+                pit: String::from(pit),
+                keep_alive: String::from(keep_alive),
+                query: String::from(query),
+                sort: String::from(sort),
+                asc,
+                cursor: None,
+            }
+        }
+    }
+
+    impl std::fmt::Display for ResumeToken {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            let cursor = self.cursor.map(|cursor| cursor.to_string()).unwrap_or_default();
+            let raw = format!("1\n{}\n{}\n{}\n{}\n{}\n{}", self.pit, self.keep_alive, self.sort, self.asc, cursor, self.query);
+            f.write_str(&base64::encode(raw.as_bytes()))
+        }
+    }
+
+    impl std::str::FromStr for ResumeToken {
+        type Err = Error;
+
+        fn from_str(token: &str) -> Result<ResumeToken, Error> {
+            let invalid = || Error::InvalidParameter(String::from("invalid resume token."));
+            let raw = base64::decode(token).and_then(|raw| String::from_utf8(raw).ok()).ok_or_else(invalid)?;
+            let fields = raw.splitn(7, '\n').collect::<Vec<&str>>();
+            match fields[..] {
+                ["1", pit, keep_alive, sort, asc, cursor, query] => Ok(ResumeToken {
+                    pit: String::from(pit),
+                    keep_alive: String::from(keep_alive),
+                    query: String::from(query),
+                    sort: String::from(sort),
+                    asc: asc.parse::<bool>().map_err(|_| invalid())?,
+                    cursor: if cursor.is_empty() { None } else { Some(cursor.parse::<Cursor>()?) },
+                }),
+                _ => Err(invalid()),
+            }
+        }
+    }
+
+    impl std::str::FromStr for Cursor {
+        type Err = Error;
+
+        fn from_str(cursor: &str) -> Result<Cursor, Error> {
+            let invalid = || Error::InvalidParameter(format!("invalid cursor {}.", cursor));
+            let (order, last_sort) = cursor.split_once(':').ok_or_else(invalid)?;
+            let asc = match order {
+                "asc" => true,
+                "desc" => false,
+                _ => return Err(invalid()),
+            };
+            Ok(Cursor { last_sort: last_sort.parse::<i64>().map_err(|_| invalid())?, asc })
+        }
+    }
+
+    /// A document returned by `Client::search_hits`.
+    #[derive(Debug, Clone)]
+    pub struct Hit {
+
+        /// The "_id" of the document. 
+        pub id: String,
+
+        /// The "_index" of the document. 
+        pub index: String,
+
+        /// The "_score" of the document, None when elasticsearch does not compute scores, e.g. when sorting by a field. 
+        pub score: Option<f64>,
+
+        /// The "_source" of the document, a json formatted string. Empty when the "_source" is not returned, 
+        /// e.g. when only `SearchOptions::fields` or `SearchOptions::docvalue_fields` are requested, then read `fields` instead. 
+        pub source: String,
+
+        /// The "fields" of the document, a json formatted object, e.g. the values of `SearchOptions::fields` and `SearchOptions::docvalue_fields`. 
+        /// Empty when no fields are returned. 
+        pub fields: String,
+
+        /// The "_seq_no" of the document, None unless `SearchOptions::seq_no_primary_term` is specified. 
+        pub seq_no: Option<i64>,
+
+        /// The "_primary_term" of the document, None unless `SearchOptions::seq_no_primary_term` is specified. 
+        pub primary_term: Option<i64>,
+    }
+
+    impl Hit {
+
+        /// Read a hit from a search response. 
+        fn from_json(hit: &EsJson) -> Result<Hit, Error> {
+            let score = match hit.find_optional_json("\"_score\"")? {
+                Some(score) => score.get_number().ok().and_then(|score| score.parse::<f64>().ok()),
+                None => None,
+            };
+            let source = match hit.find_optional_json("\"_source\"")? {
+                Some(source) => EsJsonAnalyzer::to_json(source),
+                None => String::new(),
+            };
+            let fields = match hit.find_optional_json("\"fields\"")? {
+                Some(fields) => EsJsonAnalyzer::to_json(fields),
+                None => String::new(),
+            };
+            Ok(Hit {
+                id: hit.find_json("\"_id\"")?.get_unescaped()?,
+                index: hit.find_json("\"_index\"")?.get_unescaped()?,
+                score,
+                source,
+                fields,
+                seq_no: hit.find_optional_json("\"_seq_no\"")?.map(EsJson::get_i64).transpose()?,
+                primary_term: hit.find_optional_json("\"_primary_term\"")?.map(EsJson::get_i64).transpose()?,
+            })
+        }
+    }
+
+    /// Health of the cluster returned by `Client::health`.
+    #[derive(Debug, Clone)]
+    pub struct ClusterHealth {
+
+        /// The name of the cluster. 
+        pub cluster_name: String,
+
+        /// The status of the cluster, "green", "yellow" or "red". 
+        pub status: String,
+
+        /// The number of nodes in the cluster. 
+        pub number_of_nodes: i64,
+
+        /// The number of data nodes in the cluster. 
+        pub number_of_data_nodes: i64,
+    }
+
+    /// Statistics of searches, see `SearchOptions::stats` and `Client::search_with_stats`. 
+    /// The counters are atomic, so concurrent segments and searches sharing them add up correctly. 
+    #[derive(Debug, Default)]
+    pub struct SearchStats {
+        requests: std::sync::atomic::AtomicUsize,
+        documents: std::sync::atomic::AtomicUsize,
+        bytes: std::sync::atomic::AtomicU64,
+        truncated: std::sync::atomic::AtomicBool,
+    }
+
+    impl SearchStats {
+
+        /// The number of requests sent, counts, probes, binary search steps and batches. 
+        /// The counts include the count of the documents that have the sort field, sent before a reversed query. 
+        pub fn requests(&self) -> usize {
+            self.requests.load(std::sync::atomic::Ordering::Relaxed)
+        }
+
+        /// The number of documents fetched by the batches. 
+        pub fn documents(&self) -> usize {
+            self.documents.load(std::sync::atomic::Ordering::Relaxed)
+        }
+
+        /// The number of bytes of the request and response bodies, before compression. 
+        pub fn bytes(&self) -> u64 {
+            self.bytes.load(std::sync::atomic::Ordering::Relaxed)
+        }
+
+        /// Whether a search stopped at its deadline and returned fewer documents than requested, see `SearchOptions::partial_on_deadline`. 
+        pub fn truncated(&self) -> bool {
+            self.truncated.load(std::sync::atomic::Ordering::Relaxed)
+        }
+    }
+
+    /// Plan of a search returned by `Client::explain_plan`.
+    #[derive(Debug, Clone)]
+    pub struct SearchPlan {
+
+        /// Whether the query direction is reversed because the documents are near the end of the data set. 
+        pub reverse: bool,
+
+        /// The sort order the batches are fetched in, the opposite of the requested one when reversed. 
+        pub asc: bool,
+
+        /// The from value of the first batch, after the deep paging calculation reduced it. 
+        pub new_from: i64,
+
+        /// The sort value the first batch starts after, None when from did not need to be reduced. 
+        pub start: Option<String>,
+
+        /// The query of the first batch, the original query with the range filters of the deep paging calculation. 
+        /// Empty when no documents can match. 
+        pub query: String,
+
+        /// The total number of documents that meet query conditions, if it was fetched. 
+        pub total: Option<i64>,
+
+        /// The number of requests sent to compute the plan. 
+        pub requests: usize,
+
+        /// The estimated number of batch requests to fetch the documents, fewer when the data set ends earlier. 
+        pub batches: i64,
+    }
+
+    /// Retry settings of throttled requests.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use es_deep_pager::deep_page_client;
+    /// # use std::time::Duration;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let transport = elasticsearch::http::transport::Transport::single_node("http://localhost:9200")?;
+    /// let retry = deep_page_client::Retry {
+    ///     max_retries: 5,
+    ///     initial_backoff: Duration::from_millis(500),
+    ///     max_backoff: Duration::from_secs(30),
+    ///     retry_unavailable: true,
+    /// };
+    /// let client = deep_page_client::Client::builder().transport(transport).retry(retry).build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[derive(Clone, Copy)]
+    pub struct Retry {
+
+        /// The maximum number of retries of a request, after which the last error is returned. 
+        pub max_retries: u32,
+
+        /// The wait time before the first retry, doubled for each following retry. 
+        pub initial_backoff: Duration,
+
+        /// The maximum wait time before a retry. 
+        pub max_backoff: Duration,
+
+        /// Whether 503 Service Unavailable responses are retried in addition to 429 Too Many Requests. 
+        pub retry_unavailable: bool,
+    }
+
+    impl Retry {
+
+        /// Get the wait time before the specified retry, exponential backoff with random jitter. 
+        fn backoff(&self, attempt: u32) -> Duration {
+            let backoff = self.initial_backoff
+                .checked_mul(1u32.checked_shl(attempt).unwrap_or(u32::MAX))
+                .unwrap_or(self.max_backoff)
+                .min(self.max_backoff);
+
+            // Wait a random time between half and all of the backoff, so that concurrent clients do not retry at the same time.
+            let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().subsec_nanos();
+            backoff / 2 + (backoff / 2).mul_f64((nanos % 1000) as f64 / 1000.0)
+        }
+    }
+
+    /// How the total number of documents is fetched for deep pages, see `SearchOptions::total_hits`. 
+    #[derive(Clone, Copy, Default, PartialEq)]
+    pub enum TotalHits {
+
+        /// The first probe request tracks the accurate total, falling back to a "_count" request when it is not accurate. 
+        #[default]
+        Eager,
+
+        /// The total is read from the first probe response when elasticsearch reports it accurately without tracking, 
+        /// e.g. below 10000 documents. When it is not accurate, the query direction is not reversed. 
+        Lazy,
+
+        /// The total is not fetched, the query direction is never reversed. 
+        Never,
+    }
+
+    /// Gzip compression of request and response bodies. 
+    #[cfg(feature = "gzip")]
+    mod gzip {
+        use std::io::{Read, Write};
+        use super::Error;
+
+        pub fn compress(data: &[u8]) -> Result<Vec<u8>, Error> {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data).and_then(|_| encoder.finish()).map_err(|e| Error::Message(format!("Gzip error: {}", e)))
+        }
+
+        pub fn decompress(data: &[u8]) -> Result<Vec<u8>, Error> {
+            let mut decompressed = Vec::new();
+            flate2::read::GzDecoder::new(data).read_to_end(&mut decompressed).map_err(|e| Error::Message(format!("Gzip error: {}", e)))?;
+            Ok(decompressed)
+        }
+    }
+
+    /// Url-safe base64 without padding, for the resume tokens. 
+    mod base64 {
+        const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+        pub fn encode(data: &[u8]) -> String {
+            let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+            for chunk in data.chunks(3) {
+                let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, b)| bits | (*b as u32) << (16 - 8 * i));
+                for i in 0..=chunk.len() {
+                    encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+                }
+            }
+            encoded
+        }
+
+        pub fn decode(encoded: &str) -> Option<Vec<u8>> {
+            let mut data = Vec::with_capacity(encoded.len() * 3 / 4);
+            for chunk in encoded.as_bytes().chunks(4) {
+                if chunk.len() == 1 {
+                    return None;
+                }
+                let mut bits = 0u32;
+                for (i, c) in chunk.iter().enumerate() {
+                    let value = ALPHABET.iter().position(|a| a == c)? as u32;
+                    bits |= value << (18 - 6 * i);
+                }
+                for i in 0..chunk.len() - 1 {
+                    data.push((bits >> (16 - 8 * i)) as u8);
+                }
+            }
+            Some(data)
+        }
+    }
+
+    /// Whether the value is an elasticsearch time value, a number of time units, e.g. "1m" or "500ms". 
+    fn is_time_value(value: &str) -> bool {
+        let digits = value.bytes().take_while(u8::is_ascii_digit).count();
+        digits > 0 && ["d", "h", "m", "s", "ms", "micros", "nanos"].contains(&&value[digits..])
+    }
+
+    /// Quote and escape a string, e.g. a scroll id, as a json string. 
+    fn json_string(value: &str) -> String {
+        let mut json = String::with_capacity(value.len() + 2);
+        write_json_string(value, &mut json);
+        json
+    }
+
+    /// Write a string as a quoted and escaped json string. 
+    fn write_json_string(value: &str, json: &mut String) {
+        json.push('\"');
+        for c in value.chars() {
+            match c {
+                '\"' => json.push_str("\\\""),
+                '\\' => json.push_str("\\\\"),
+                '\n' => json.push_str("\\n"),
+                '\r' => json.push_str("\\r"),
+                '\t' => json.push_str("\\t"),
+                '\u{8}' => json.push_str("\\b"),
+                '\u{c}' => json.push_str("\\f"),
+                c if (c as u32) < 0x20 => { let _ = write!(json, "\\u{:04x}", c as u32); }
+                c => json.push(c),
+            }
+        }
+        json.push('\"');
+    }
+
+    /// Percent-encode a url parameter value. 
+    fn encode_url_component(value: &str) -> String {
+        let mut encoded = String::new();
+        for byte in value.bytes() {
+            if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+                encoded.push(byte as char);
+            } else {
+                encoded.push_str(&format!("%{:02X}", byte));
+            }
+        }
+        encoded
+    }
+
+    /// Numeric type of the unique number sort field.
+    trait SortValue: Copy + PartialOrd + std::fmt::Display {
+
+        /// Type name used in error messages.
+        const NAME: &'static str;
+
+        /// Parse a sort value from its json literal.
+        fn parse(value: &str) -> Option<Self>;
+
+        /// Get a value between low and high, None if no value lies strictly between them.
+        fn midpoint(low: Self, high: Self) -> Option<Self>;
+    }
+
+    impl SortValue for i64 {
+        const NAME: &'static str = "integer";
+
+        fn parse(value: &str) -> Option<i64> {
+            value.parse::<i64>().ok()
+        }
+
+        fn midpoint(low: i64, high: i64) -> Option<i64> {
+            // Compute in i128, the distance between the extremes of i64 does not fit in i64.
+            let sort_abs = high as i128 - low as i128;
+            if sort_abs <= 1 {
+                return None;
+            }
+            Some((low as i128 + sort_abs / 2) as i64)
+        }
+    }
+
+    impl SortValue for u64 {
+        const NAME: &'static str = "unsigned integer";
+
+        fn parse(value: &str) -> Option<u64> {
+            value.parse::<u64>().ok()
+        }
+
+        fn midpoint(low: u64, high: u64) -> Option<u64> {
+            let sort_abs = high.checked_sub(low)?;
+            if sort_abs <= 1 {
+                return None;
+            }
+            Some(low + sort_abs / 2)
+        }
+    }
+
+    impl SortValue for f64 {
+        const NAME: &'static str = "double";
+
+        fn parse(value: &str) -> Option<f64> {
+            value.parse::<f64>().ok().filter(|v| v.is_finite())
+        }
+
+        fn midpoint(low: f64, high: f64) -> Option<f64> {
+            // Halve before adding, so that values of very large magnitude do not overflow.
+            let mid = low / 2.0 + high / 2.0;
+            if mid > low && mid < high {
+                Some(mid)
+            } else {
+                None
+            }
+        }
+    }
+
+    /// The total number of documents and the minimum and maximum sort values that meet query conditions. 
+    struct Bounds<V> {
+        total: Option<i64>,
+        min: Option<V>,
+        max: Option<V>,
+
+        // number of documents that have the sort field, counted when a query is first reversed.
+        valued_total: Option<i64>,
+    }
+
+    /// Cursor state of a deep paging traversal.
+    #[derive(Default)]
+    struct Traversal {
+        query: String,
+        sort: String,
+        range_query: RangeQuery,
+
+        // sort clause and source filter of the batches, built by the first one.
+        sort_clause: String,
+        source: Option<Option<String>>,
+
+        tie_break: Option<String>,
+        dedup: bool,
+        boundary_value: String,
+        boundary_ids: Vec<String>,
+        asc: bool,
+        reverse: bool,
+        next_query: String,
+        next_from: i64,
+        remain_size: i64,
+
+        // sort value the first batch starts after, if from was reduced by a range filter.
+        start: Option<String>,
+
+        // total number of documents that meet query conditions, if it was fetched.
+        total: Option<i64>,
+
+        // latest point in time id returned by the batches, the following batches use it instead of the one of the options.
+        pit: Option<String>,
+
+        // whether the point in time was opened by the traversal, it is closed when the traversal finishes.
+        own_pit: bool,
+
+        // sort values of the last document, the batches continue with "search_after" instead of range queries when set.
+        search_after: Option<String>,
+
+        // number of neighbor documents fetched before and after the window to order the tied documents by "_id".
+        lead: usize,
+        trail: usize,
+
+        // number of requests issued and batches fetched by the traversal.
+        #[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+        requests: usize,
+        #[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+        batches: usize,
+    }
+
+    /// Range restrictions of a query on the sort field. 
+    /// The text before the bounds, the query and the sort field, is written once, and copied into each restricted query 
+    /// of the batches and binary search steps, so only the bounds are rendered for each of them. 
+    #[derive(Default)]
+    struct RangeQuery {
+        prefix: String,
+    }
+
+    impl RangeQuery {
+        const SUFFIX: &'static str = "}}}}}";
+
+        fn new(query: &str, sort: &str) -> RangeQuery {
+            let mut prefix = String::with_capacity(query.len() + sort.len() + 48);
+            let _ = write!(prefix, "{{\"bool\":{{\"must\":{},\"filter\":{{\"range\":{{\"{}\":{{", query, sort);
+            RangeQuery { prefix }
+        }
+
+        /// The query restricted to sort values between `start` and `end` inclusively. 
+        fn range<V: SortValue>(&self, start: V, end: V) -> String {
+            let mut json = String::with_capacity(self.prefix.len() + 96);
+            json.push_str(&self.prefix);
+            let _ = write!(json, "\"gte\":{},\"lte\":{}", start, end);
+            json.push_str(Self::SUFFIX);
+            json
+        }
+
+        /// The query restricted to sort values compared with `value` by `cmp`, e.g. "gt". 
+        fn cmp<V: SortValue>(&self, cmp: &str, value: V) -> String {
+            let mut json = String::with_capacity(self.prefix.len() + 64);
+            json.push_str(&self.prefix);
+            let _ = write!(json, "\"{}\":{}", cmp, value);
+            json.push_str(Self::SUFFIX);
+            json
+        }
+    }
+
+    /// json struct, the parsed value of the `json` module. 
+    /// It implements `Drop` to drop deeply nested values without recursion, so the variants are matched by reference, 
+    /// and taken with `into_array`, `into_object` and `into_string`. 
+    pub enum EsJson {
+        /// The items of an array in their order. 
+        Array(Vec<EsJson>),
+        /// The key-value pairs of an object in their order, the keys are raw json strings including the surrounding quotes. 
+        /// Derefs to the slice of the pairs, see `JsonObject`. 
+        Object(JsonObject),
+        /// A raw json string including the surrounding quotes, escape sequences are kept undecoded. 
+        String(String),
+        /// A raw json number, kept as written so that large integers and decimals lose no precision. 
+        Number(String),
+        /// A true or false literal. 
+        Bool(bool),
+        /// The null literal. 
+        Null,
+    }
+
+    /// Objects with at least this many keys get a key index when they are built. 
+    /// Below it a linear scan over the keys is faster than hashing the key. 
+    const OBJECT_INDEX_MIN_KEYS : usize = 16;
+
+    /// The key-value pairs of a json object in their order, derefs to the slice of the pairs. 
+    /// Wide objects, e.g. the "_source" of documents with hundreds of fields, carry an index of the first position of each key, 
+    /// built once with the object, so each lookup does not scan all the keys before it. 
+    #[derive(Default)]
+    pub struct JsonObject {
+        entries: Vec<(String, EsJson)>,
+        // first position of each decoded key, for objects of at least OBJECT_INDEX_MIN_KEYS keys.
+        index: Option<std::collections::HashMap<String, usize>>,
+    }
+
+    impl JsonObject {
+        fn new(entries: Vec<(String, EsJson)>) -> JsonObject {
+            let mut object = JsonObject { entries, index: None };
+            if object.entries.len() >= OBJECT_INDEX_MIN_KEYS {
+                let mut index = std::collections::HashMap::with_capacity(object.entries.len());
+                for (i, (key, _)) in object.entries.iter().enumerate() {
+                    if let Some(key) = decode_raw_string(key) {
+                        index.entry(key.into_owned()).or_insert(i);
+                    }
+                }
+                object.index = Some(index);
+            }
+            object
+        }
+
+        /// The position of the first pair of a decoded key. 
+        fn position(&self, key: &str) -> Option<usize> {
+            match &self.index {
+                Some(index) => index.get(key).copied(),
+                // most keys have no escape sequences, their content is compared without decoding them.
+                None => self.entries.iter().position(|(k, _)| match k.get(1..k.len().saturating_sub(1)) {
+                    Some(content) if !content.contains('\\') => content == key,
+                    _ => decode_raw_string(k).is_some_and(|k| k == key),
+                }),
+            }
+        }
+
+        /// Append a pair of a raw key, keeping the index. 
+        fn push(&mut self, key: String, value: EsJson) {
+            if let (Some(index), Some(decoded)) = (&mut self.index, decode_raw_string(&key)) {
+                index.entry(decoded.into_owned()).or_insert(self.entries.len());
+            }
+            self.entries.push((key, value));
+        }
+    }
+
+    impl std::ops::Deref for JsonObject {
+        type Target = [(String, EsJson)];
+
+        fn deref(&self) -> &[(String, EsJson)] {
+            &self.entries
+        }
+    }
+
+    impl From<Vec<(String, EsJson)>> for JsonObject {
+        fn from(entries: Vec<(String, EsJson)>) -> JsonObject {
+            JsonObject::new(entries)
+        }
+    }
+
+    impl<'a> IntoIterator for &'a JsonObject {
+        type Item = &'a (String, EsJson);
+        type IntoIter = std::slice::Iter<'a, (String, EsJson)>;
+
+        fn into_iter(self) -> Self::IntoIter {
+            self.entries.iter()
+        }
+    }
+
+    /// json struct view functions
+    impl EsJson {
+        fn get_array(&self) -> Result<&Vec<EsJson>, Error> {
+            match self {
+                EsJson::Array(arr) => Ok(arr),
+                _ => Err(Error::invalid_json()),
+            }
+        }
+
+        fn get_object(&self) -> Result<&JsonObject, Error> {
+            match self {
+                EsJson::Object(obj) => Ok(obj),
+                _ => Err(Error::invalid_json()),
+            }
+        }
+
+        fn get_string(&self) -> Result<&String, Error> {
+            match self {
+                EsJson::String(s) => Ok(s),
+                _ => Err(Error::invalid_json()),
+            }
+        }
+
+        fn get_number(&self) -> Result<&String, Error> {
+            match self {
+                EsJson::Number(n) => Ok(n),
+                _ => Err(Error::invalid_json()),
+            }
+        }
+
+        fn get_bool(&self) -> Result<bool, Error> {
+            match self {
+                EsJson::Bool(b) => Ok(*b),
+                _ => Err(Error::invalid_json()),
+            }
+        }
+
+        /// Get the content of a json string without the surrounding quotes, or the text of a number. 
+        fn get_unquoted(&self) -> Result<&str, Error> {
+            match self {
+                EsJson::String(s) => Ok(s.strip_prefix('\"').and_then(|s| s.strip_suffix('\"')).unwrap_or(s)),
+                EsJson::Number(n) => Ok(n),
+                _ => Err(Error::invalid_json()),
+            }
+        }
+
+        /// Get the value of an integer, either a number literal like 10000 or a string holding one like "10000", 
+        /// as elasticsearch returns index settings as strings. 
+        fn get_i64(&self) -> Result<i64, Error> {
+            match self.get_unquoted()?.parse::<i64>() {
+                Ok(value) => Ok(value),
+                Err(e) => Err(Error::Parse { position: None, detail: format!("Parse error: {}", e) }),
+            }
+        }
+
+        /// Get the decoded content of a json string, with the surrounding quotes stripped and escape sequences decoded. 
+        /// Literals are returned as they are written. 
+        fn get_unescaped(&self) -> Result<String, Error> {
+            match self {
+                EsJson::String(s) => match s.strip_prefix('\"').and_then(|s| s.strip_suffix('\"')) {
+                    Some(content) => unescape_json_string(content),
+                    None => Ok(s.to_string()),
+                },
+                EsJson::Number(_) | EsJson::Bool(_) | EsJson::Null => Ok(EsJsonAnalyzer::to_json(self)),
+                _ => Err(Error::invalid_json()),
+            }
+        }
+
+        /// Find the value of a raw key in a json object, the first one when the key is duplicated, see `json::Value::get_all`. 
+        fn find_optional_json(&self, key: &str) -> Result<Option<&EsJson>, Error> {
+            let obj = self.get_object()?;
+            let key = key.strip_prefix('\"').and_then(|key| key.strip_suffix('\"')).unwrap_or(key);
+            Ok(obj.position(key).map(|i| &obj[i].1))
+        }
+
+        fn find_json(&self, key: &str) -> Result<&EsJson, Error> {
+            match self.find_optional_json(key)? {
+                Some(v) => Ok(v),
+                None => Err(Error::invalid_json()),
+            }
+        }
+
+        /// Find the value of a dotted field path like "meta.seq" in a json object, descending through nested objects. 
+        /// A flattened key that contains the dots itself is also accepted. 
+        fn find_path(&self, path: &str) -> Result<&EsJson, Error> {
+            if let Some(value) = self.find_optional_json(&format!("\"{}\"", path))? {
+                return Ok(value);
+            }
+            match path.split_once('.') {
+                Some((head, rest)) => self.find_json(&format!("\"{}\"", head))?.find_path(rest),
+                None => Err(Error::invalid_json()),
+            }
+        }
+
+        /// Get the total number of hits of a search response. 
+        /// Returns None if the total is absent or is only a lower bound. 
+        /// On elasticsearch 6 and lower the total is a number, on elasticsearch 7 and higher it is an object 
+        /// like {"value":N,"relation":"eq"}. 
+        fn get_total(&self) -> Result<Option<i64>, Error> {
+            let total = match self.find_json("\"hits\"")?.find_optional_json("\"total\"")? {
+                Some(total) => total,
+                None => return Ok(None),
+            };
+            match total {
+                EsJson::Object(_) => {
+                    if total.find_json("\"relation\"")?.get_string()? != "\"eq\"" {
+                        return Ok(None);
+                    }
+                    Ok(Some(total.find_json("\"value\"")?.get_i64()?))
+                }
+                _ => Ok(Some(total.get_i64()?)),
+            }
+        }
+
+        /// Check the "_shards" section of a search response, returns an error listing the failure reasons if any shard failed. 
+        fn check_shards(&self) -> Result<(), Error> {
+            let shards = match self.find_optional_json("\"_shards\"")? {
+                Some(shards) => shards,
+                None => return Ok(()),
+            };
+            let failed = shards.find_json("\"failed\"")?.get_i64()?;
+            if failed == 0 {
+                return Ok(());
+            }
+            let total = shards.find_json("\"total\"")?.get_i64()?;
+            let mut reasons = vec![];
+            if let Some(failures) = shards.find_optional_json("\"failures\"")? {
+                for failure in failures.get_array()? {
+                    let index = failure.find_json("\"index\"").and_then(|v| v.get_unescaped()).unwrap_or_default();
+                    let shard = failure.find_json("\"shard\"").and_then(|v| v.get_unescaped()).unwrap_or_default();
+                    let reason = failure.find_json("\"reason\"").and_then(|v| v.find_json("\"reason\"")).and_then(|v| v.get_unescaped()).unwrap_or_default();
+                    reasons.push(format!("[{}][{}] {}", index, shard, reason));
+                }
+            }
+            Err(Error::Message(format!("{} of {} shards failed: {}", failed, total, reasons.join("; "))))
+        }
+
+        /// Check the "timed_out" field of a search response. 
+        fn is_timed_out(&self) -> Result<bool, Error> {
+            match self.find_optional_json("\"timed_out\"")? {
+                Some(timed_out) => timed_out.get_bool(),
+                None => Ok(false),
+            }
+        }
+
+        /// Add an empty "hits.hits" array to a search response without one, e.g. a filtered response without documents. 
+        fn fill_hits(&mut self) {
+            let obj = match self {
+                EsJson::Object(obj) => obj,
+                _ => return,
+            };
+            let hits = match obj.position("hits") {
+                Some(i) => i,
+                None => {
+                    obj.push(String::from("\"hits\""), EsJson::Object(JsonObject::default()));
+                    obj.len() - 1
+                }
+            };
+            if let EsJson::Object(hits) = &mut obj.entries[hits].1 {
+                if hits.position("hits").is_none() {
+                    hits.push(String::from("\"hits\""), EsJson::Array(vec![]));
+                }
+            }
+        }
+
+        fn get_hits(&self) -> Result<&Vec<EsJson>, Error> {
+            let hits = self.find_json("\"hits\"")?.find_json("\"hits\"")?.get_array()?;
+            Ok(hits)
+        }
+    }
+
+    /// Values are dropped with an explicit stack of the nested ones instead of recursion, 
+    /// so a deeply nested json parsed with a large depth limit does not overflow the thread stack. 
+    impl Drop for EsJson {
+        fn drop(&mut self) {
+            let is_nested = |value: &EsJson| matches!(value, EsJson::Array(_) | EsJson::Object(_));
+            let mut nested = vec![];
+            match self {
+                EsJson::Array(ary) if ary.iter().any(is_nested) => nested.append(ary),
+                EsJson::Object(obj) if obj.iter().any(|(_, value)| is_nested(value)) => nested.extend(obj.entries.drain(..).map(|(_, value)| value)),
+                _ => return,
+            }
+            while let Some(mut value) = nested.pop() {
+                match &mut value {
+                    EsJson::Array(ary) => nested.append(ary),
+                    EsJson::Object(obj) => nested.extend(obj.entries.drain(..).map(|(_, value)| value)),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Minimal json reader of the client, to read elasticsearch responses without depending on serde_json. 
+    /// 
+    /// This is the same parser the client uses for its own requests. It is a small reader, not a general-purpose json library: 
+    /// strings and numbers are kept as the raw text of the input and only decoded when asked, object keys keep their order 
+    /// and duplicates, and values can not be built or modified. 
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # fn main() -> Result<(), es_deep_pager::deep_page_client::Error> {
+    /// use es_deep_pager::deep_page_client::json;
+    /// 
+    /// let value = json::parse("{\"hits\":{\"total\":{\"value\":12},\"hits\":[{\"_id\":\"a\\u00e9\"}]},\"timed_out\":false}")?;
+    /// let total = value.get("hits").and_then(|v| v.get("total")).and_then(|v| v.get("value")).and_then(|v| v.as_i64());
+    /// assert_eq!(total, Some(12));
+    /// assert_eq!(value.get("timed_out").and_then(|v| v.as_bool()), Some(false));
+    /// 
+    /// // the owned parts of a value are taken with the `into_` methods.
+    /// let hits = value.get("hits").and_then(|v| v.get("hits")).map(|v| v.to_string()).unwrap_or_default();
+    /// let ids = json::parse(&hits)?.into_array().unwrap_or_default().into_iter()
+    ///     .filter_map(|hit| hit.into_object())
+    ///     .flat_map(|pairs| pairs.into_iter().filter(|(key, _)| key == "\"_id\"").filter_map(|(_, id)| id.into_string()))
+    ///     .collect::<Vec<String>>();
+    /// assert_eq!(ids, ["aé"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub mod json {
+        use super::{decode_raw_string, Error, EsJson, EsJsonAnalyzer};
+
+        /// A parsed json value. 
+        pub use super::EsJson as Value;
+
+        /// Parse a json string, a single json value optionally surrounded by whitespace, anything after the value is malformed. 
+        /// Arrays and objects may be nested 256 levels deep, deeper json fails with `Error::Parse` "json nesting too deep". 
+        pub fn parse(json: &str) -> Result<Value, Error> {
+            EsJsonAnalyzer::from_json(json)
+        }
+
+        /// Parse a json string, same as `parse`, but with nesting up to `max_depth` levels, e.g. for deeply nested documents. 
+        /// The parser, the serializer and the drop of the value keep their own stacks, so a large `max_depth` does not need 
+        /// a large thread stack. 
+        pub fn parse_with_depth(json: &str, max_depth: usize) -> Result<Value, Error> {
+            EsJsonAnalyzer::from_json_with_depth(json, max_depth)
+        }
+
+        impl EsJson {
+
+            /// Get the value of a key of an object, the first one when the key is duplicated. 
+            /// The keys are compared decoded, so "caf\u00e9" is found by "café". 
+            /// Wide objects are looked up in their key index, see `JsonObject`. 
+            /// None when this is not an object or the key is absent. 
+            pub fn get(&self, key: &str) -> Option<&EsJson> {
+                match self {
+                    EsJson::Object(obj) => obj.position(key).map(|i| &obj[i].1),
+                    _ => None,
+                }
+            }
+
+            /// Get every value of a key of an object in their order, as json allows duplicate keys. 
+            /// The keys are compared decoded, same as `get`. 
+            /// Empty when this is not an object or the key is absent. 
+            pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a EsJson> + 'a {
+                let (obj, first) = match self {
+                    EsJson::Object(obj) => (&obj[..], obj.position(key).unwrap_or(obj.len())),
+                    _ => (&[][..], 0),
+                };
+                obj[first..].iter().filter(move |(k, _)| decode_raw_string(k).is_some_and(|k| k == key)).map(|(_, v)| v)
+            }
+
+            /// Get the item at a position of an array. 
+            /// None when this is not an array or the position is out of bounds. 
+            pub fn index(&self, n: usize) -> Option<&EsJson> {
+                match self {
+                    EsJson::Array(ary) => ary.get(n),
+                    _ => None,
+                }
+            }
+
+            /// Get the content of a string without the surrounding quotes, with its escape sequences decoded, 
+            /// borrowed from the raw json when it has none. 
+            /// None when this is not a string, or the string was built with an invalid escape sequence. 
+            pub fn as_str(&self) -> Option<std::borrow::Cow<'_, str>> {
+                match self {
+                    EsJson::String(s) => decode_raw_string(s),
+                    _ => None,
+                }
+            }
+
+            /// Get the raw content of a string without the surrounding quotes, its escape sequences are not decoded, 
+            /// e.g. to copy it into another json. None when this is not a string. 
+            pub fn as_raw_str(&self) -> Option<&str> {
+                match self {
+                    EsJson::String(s) => s.strip_prefix('\"').and_then(|s| s.strip_suffix('\"')),
+                    _ => None,
+                }
+            }
+
+            /// Get the value of an integer number. None when this is not a number or does not fit in i64. 
+            pub fn as_i64(&self) -> Option<i64> {
+                match self {
+                    EsJson::Number(n) => n.parse::<i64>().ok(),
+                    _ => None,
+                }
+            }
+
+            /// Get the value of a number. None when this is not a number. 
+            pub fn as_f64(&self) -> Option<f64> {
+                match self {
+                    EsJson::Number(n) => n.parse::<f64>().ok(),
+                    _ => None,
+                }
+            }
+
+            /// Get the value of a boolean. None when this is not a boolean. 
+            pub fn as_bool(&self) -> Option<bool> {
+                match self {
+                    EsJson::Bool(b) => Some(*b),
+                    _ => None,
+                }
+            }
+
+            /// Check whether this is null. 
+            pub fn is_null(&self) -> bool {
+                matches!(self, EsJson::Null)
+            }
+
+            /// Take the items of an array. None when this is not an array. 
+            /// `EsJson` implements `Drop`, so its variants can not be moved out with a match, take them with the `into_` methods. 
+            pub fn into_array(mut self) -> Option<Vec<EsJson>> {
+                match &mut self {
+                    EsJson::Array(ary) => Some(std::mem::take(ary)),
+                    _ => None,
+                }
+            }
+
+            /// Take the key-value pairs of an object in their order, the keys are raw json strings including the surrounding quotes. 
+            /// None when this is not an object. 
+            pub fn into_object(mut self) -> Option<Vec<(String, EsJson)>> {
+                match &mut self {
+                    EsJson::Object(obj) => Some(std::mem::take(&mut obj.entries)),
+                    _ => None,
+                }
+            }
+
+            /// Take the content of a string without the surrounding quotes, with its escape sequences decoded, same as `as_str`. 
+            /// None when this is not a string, or the string was built with an invalid escape sequence. 
+            pub fn into_string(mut self) -> Option<String> {
+                match &mut self {
+                    EsJson::String(s) if !s.contains('\\') && s.len() >= 2 && s.starts_with('\"') && s.ends_with('\"') => {
+                        let mut content = std::mem::take(s);
+                        content.pop();
+                        content.remove(0);
+                        Some(content)
+                    }
+                    EsJson::String(s) => decode_raw_string(s).map(std::borrow::Cow::into_owned),
+                    _ => None,
+                }
+            }
+        }
+
+        impl std::fmt::Display for EsJson {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(&EsJsonAnalyzer::to_json(self))
+            }
+        }
+    }
+
+    /// Decode a raw json string including the surrounding quotes, borrowed when it has no escape sequences. 
+    /// None when the quotes are missing or an escape sequence is invalid. 
+    fn decode_raw_string(raw: &str) -> Option<std::borrow::Cow<'_, str>> {
+        let content = raw.strip_prefix('\"').and_then(|s| s.strip_suffix('\"'))?;
+        match content.contains('\\') {
+            true => unescape_json_string(content).ok().map(std::borrow::Cow::Owned),
+            false => Some(std::borrow::Cow::Borrowed(content)),
+        }
+    }
+
+    /// Deserialization of parsed json values with serde. Requires the `serde` feature. 
+    /// `Client::search_as` reads each "_source" into its type directly from the parsed response, 
+    /// instead of serializing it to text and parsing that again. 
+    #[cfg(feature = "serde")]
+    mod de {
+        use super::{decode_raw_string, EsJson};
+        use serde::de::{self, value::{Error, MapAccessDeserializer, MapDeserializer, SeqDeserializer}, IntoDeserializer, Visitor};
+        use std::borrow::Cow;
+
+        fn decode(raw: &str) -> Result<Cow<'_, str>, Error> {
+            decode_raw_string(raw).ok_or_else(|| de::Error::custom(format!("invalid json string {}", raw)))
+        }
+
+        fn visit_string<'de, V: Visitor<'de>>(raw: &'de str, visitor: V) -> Result<V::Value, Error> {
+            match decode(raw)? {
+                Cow::Borrowed(s) => visitor.visit_borrowed_str(s),
+                Cow::Owned(s) => visitor.visit_string(s),
+            }
+        }
+
+        fn object(obj: &[(String, EsJson)]) -> MapDeserializer<'_, impl Iterator<Item = (Key<'_>, &EsJson)>, Error> {
+            MapDeserializer::new(obj.iter().map(|(key, value)| (Key(key), value)))
+        }
+
+        impl<'de> de::Deserializer<'de> for &'de EsJson {
+            type Error = Error;
+
+            fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                match self {
+                    EsJson::Null => visitor.visit_unit(),
+                    EsJson::Bool(b) => visitor.visit_bool(*b),
+                    EsJson::Number(n) => match (n.parse::<i64>(), n.parse::<u64>(), n.parse::<f64>()) {
+                        (Ok(n), _, _) => visitor.visit_i64(n),
+                        (_, Ok(n), _) => visitor.visit_u64(n),
+                        (_, _, Ok(n)) => visitor.visit_f64(n),
+                        _ => Err(de::Error::custom(format!("invalid json number {}", n))),
+                    },
+                    EsJson::String(s) => visit_string(s, visitor),
+                    EsJson::Array(ary) => {
+                        let mut seq = SeqDeserializer::new(ary.iter());
+                        let value = visitor.visit_seq(&mut seq)?;
+                        seq.end()?;
+                        Ok(value)
+                    }
+                    EsJson::Object(obj) => {
+                        let mut map = object(obj);
+                        let value = visitor.visit_map(&mut map)?;
+                        map.end()?;
+                        Ok(value)
+                    }
+                }
+            }
+
+            fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                match self {
+                    EsJson::Null => visitor.visit_none(),
+                    _ => visitor.visit_some(self),
+                }
+            }
+
+            fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+                visitor.visit_newtype_struct(self)
+            }
+
+            /// Enums are read like serde_json writes them, a unit variant is a string, other variants are an object 
+            /// with the variant name as its only key. 
+            fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+                match self {
+                    EsJson::String(s) => visitor.visit_enum(IntoDeserializer::<Error>::into_deserializer(decode(s)?.into_owned())),
+                    EsJson::Object(obj) if obj.len() == 1 => visitor.visit_enum(MapAccessDeserializer::new(object(obj))),
+                    _ => self.deserialize_any(visitor),
+                }
+            }
+
+            serde::forward_to_deserialize_any! {
+                bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf unit unit_struct seq tuple 
+                tuple_struct map struct identifier ignored_any
+            }
+        }
+
+        impl<'de> IntoDeserializer<'de, Error> for &'de EsJson {
+            type Deserializer = Self;
+
+            fn into_deserializer(self) -> Self {
+                self
+            }
+        }
+
+        /// A raw key of an object, deserialized as its decoded string. 
+        struct Key<'de>(&'de str);
+
+        impl<'de> de::Deserializer<'de> for Key<'de> {
+            type Error = Error;
+
+            fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                visit_string(self.0, visitor)
+            }
+
+            serde::forward_to_deserialize_any! {
+                bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option unit unit_struct 
+                newtype_struct seq tuple tuple_struct map struct enum identifier ignored_any
+            }
+        }
+
+        impl<'de> IntoDeserializer<'de, Error> for Key<'de> {
+            type Deserializer = Self;
+
+            fn into_deserializer(self) -> Self {
+                self
+            }
+        }
+    }
+
+    /// Decode the escape sequences of a json string content. 
+    fn unescape_json_string(content: &str) -> Result<String, Error> {
+        if !content.contains('\\') {
+            return Ok(content.to_string());
+        }
+        let invalid = || Error::Parse { position: None, detail: format!("invalid escape sequence in json string {}", content) };
+        let mut string = String::with_capacity(content.len());
+        let mut chars = content.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                string.push(c);
+                continue;
+            }
+            match chars.next().ok_or_else(invalid)? {
+                '\"' => string.push('\"'),
+                '\\' => string.push('\\'),
+                '/' => string.push('/'),
+                'b' => string.push('\u{8}'),
+                'f' => string.push('\u{c}'),
+                'n' => string.push('\n'),
+                'r' => string.push('\r'),
+                't' => string.push('\t'),
+                'u' => {
+                    let mut code = read_unicode_escape(&mut chars).ok_or_else(invalid)?;
+                    // characters beyond the basic multilingual plane are escaped as a utf-16 surrogate pair.
+                    if (0xD800..0xDC00).contains(&code) {
+                        if chars.next() != Some('\\') || chars.next() != Some('u') {
+                            return Err(invalid());
+                        }
+                        let low = read_unicode_escape(&mut chars).ok_or_else(invalid)?;
+                        if !(0xDC00..0xE000).contains(&low) {
+                            return Err(invalid());
+                        }
+                        code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                    }
+                    string.push(char::from_u32(code).ok_or_else(invalid)?);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(string)
+    }
+
+    /// Check a literal against the json number grammar: an optional minus, an integer part without leading zeros, 
+    /// an optional fraction and an optional exponent, e.g. -0, 12.5, 1.5e10, 1.0E-5. 
+    fn is_json_number(literal: &str) -> bool {
+        let bytes = literal.as_bytes();
+        let mut i = 0;
+        let digits = |i: &mut usize| {
+            let start = *i;
+            while *i < bytes.len() && bytes[*i].is_ascii_digit() {
+                *i += 1;
+            }
+            *i - start
+        };
+        if bytes.get(i) == Some(&b'-') {
+            i += 1;
+        }
+        match digits(&mut i) {
+            0 => return false,
+            n if n > 1 && bytes[i - n] == b'0' => return false,
+            _ => {}
+        }
+        if bytes.get(i) == Some(&b'.') {
+            i += 1;
+            if digits(&mut i) == 0 {
+                return false;
+            }
+        }
+        if matches!(bytes.get(i), Some(b'e' | b'E')) {
+            i += 1;
+            if matches!(bytes.get(i), Some(b'+' | b'-')) {
+                i += 1;
+            }
+            if digits(&mut i) == 0 {
+                return false;
+            }
+        }
+        i == bytes.len()
+    }
+
+    /// Read the 4 hex digits of a \uXXXX escape sequence. 
+    fn read_unicode_escape(chars: &mut std::str::Chars) -> Option<u32> {
+        let mut code = 0;
+        for _ in 0..4 {
+            code = code * 16 + chars.next()?.to_digit(16)?;
+        }
+        Some(code)
+    }
+
+    /// Simple json analyzer. 
+    /// In order to keep dependencies low, use this own json analyzer. 
+    /// The json is analyzed in place, the position is a byte offset into it, and strings and literals are sliced out of it. 
+    struct EsJsonAnalyzer<'a> {
+        json: &'a str,
+        length: usize,
+        position: usize,
+        character: char,
+        max_depth: usize,
+    }
+
+    /// Default maximum nesting depth of the arrays and objects of a parsed json, deeper values are rejected, 
+    /// see `ClientBuilder::max_json_depth`. 
+    const DEFAULT_MAX_JSON_DEPTH : usize = 256;
+
+    /// An array or object of the json being read, with the key of the value being read for objects. 
+    enum OpenJson {
+        Array(Vec<EsJson>),
+        Object(Vec<(String, EsJson)>, String),
+    }
+
+    impl<'a> EsJsonAnalyzer<'a> {
+
+        /// Create a json analyzer.
+        fn new(str: &'a str) -> Result<EsJsonAnalyzer<'a>, Error> {
+            let character = match str.chars().next() {
+                Some(character) => character,
+                None => return Err(Error::Parse { position: None, detail: String::from("json can not be empty.") }),
+            };
+            Ok(EsJsonAnalyzer {
+                position: 0,
+                character,
+                length: str.len(),
+                json: str,
+                max_depth: DEFAULT_MAX_JSON_DEPTH,
+            })
+        }
+
+        /// Goto next json character.
+        fn goto_next_char(&mut self) -> bool {
+            if self.position >= self.length {
+                return false;
+            }
+            self.position += self.character.len_utf8();
+            match self.json[self.position..].chars().next() {
+                Some(character) => {
+                    self.character = character;
+                    true
+                }
+                None => {
+                    // past the end, so a truncated json does not read its last character again.
+                    self.character = '\0';
+                    false
+                }
+            }
+        }
+
+        /// Count the number of consecutive occurrences of a specified ascii character before the current character
+        fn count_prev_char(&self, prev_char: u8) -> usize {
+            self.json.as_bytes()[..self.position].iter().rev().take_while(|&&char| char == prev_char).count()
+        }
+
+        /// Skip space characters.
+        fn skip_space(&mut self) -> i32 {
+            let mut skip_count = 0;
+            const SPACE_CHARS: [char; 4] = [' ', '\t', '\n', '\r'];
+            while SPACE_CHARS.contains(&self.character) {
+                if !self.goto_next_char() {
+                    break;
+                }
+                skip_count += 1;
+            }
+            skip_count
+        }
+
+        /// Build a malformed json error at the current position.
+        fn malformed(&self) -> Error {
+            Error::Parse { position: Some(self.position), detail: String::from("malformed json") }
+        }
+
+        /// Read a json value. 
+        /// Nested arrays and objects are read with an explicit stack of the open ones instead of recursion, 
+        /// so deeply nested json is rejected by the `max_depth` check instead of overflowing the thread stack. 
+        fn read_json_value(&mut self) -> Result<EsJson, Error> {
+            let mut open: Vec<OpenJson> = vec![];
+            loop {
+                self.skip_space();
+                let mut value = match self.character {
+                    '{' | '[' if open.len() >= self.max_depth => return Err(Error::Parse { position: Some(self.position), detail: String::from("json nesting too deep") }),
+                    '{' => {
+                        self.goto_next_char();
+                        self.skip_space();
+                        if self.character == '}' {
+                            self.goto_next_char();
+                            EsJson::Object(JsonObject::default())
+                        } else {
+                            let key = self.read_json_key()?;
+                            open.push(OpenJson::Object(vec![], key));
+                            continue;
+                        }
+                    }
+                    '[' => {
+                        self.goto_next_char();
+                        self.skip_space();
+                        if self.character == ']' {
+                            self.goto_next_char();
+                            EsJson::Array(vec![])
+                        } else {
+                            open.push(OpenJson::Array(vec![]));
+                            continue;
+                        }
+                    }
+                    '\"' => EsJson::String(self.read_json_string()?),
+                    _ => self.read_json_literal()?,
+                };
+
+                // add the value to the innermost open array or object, closing the ones that end after it.
+                loop {
+                    let parent = match open.last_mut() {
+                        Some(parent) => parent,
+                        None => return Ok(value),
+                    };
+                    self.skip_space();
+                    match parent {
+                        OpenJson::Array(ary) => {
+                            ary.push(value);
+                            match self.character {
+                                ',' => {
+                                    self.goto_next_char();
+                                    break;
+                                }
+                                ']' => {
+                                    self.goto_next_char();
+                                    value = EsJson::Array(std::mem::take(ary));
+                                }
+                                _ => return Err(self.malformed()),
+                            }
+                        }
+                        OpenJson::Object(obj, key) => {
+                            obj.push((std::mem::take(key), value));
+                            match self.character {
+                                ',' => {
+                                    self.goto_next_char();
+                                    *key = self.read_json_key()?;
+                                    break;
+                                }
+                                '}' => {
+                                    self.goto_next_char();
+                                    value = EsJson::Object(JsonObject::new(std::mem::take(obj)));
+                                }
+                                _ => return Err(self.malformed()),
+                            }
+                        }
+                    }
+                    open.pop();
+                }
+            }
+        }
+
+        /// Read the key of an object member and the colon after it. 
+        fn read_json_key(&mut self) -> Result<String, Error> {
+            self.skip_space();
+            let key = self.read_json_string()?;
+            self.skip_space();
+            if self.character != ':' {
+                return Err(self.malformed());
+            }
+            self.goto_next_char();
+            Ok(key)
+        }
+
+        /// Read a json literal, a number, true, false or null.
+        /// Only true, false, null and json numbers are accepted, anything else, e.g. NaN or a stray quote, is malformed. 
+        fn read_json_literal(&mut self) -> Result<EsJson, Error> {
+            const STOP_CHARS: [char; 7] = [' ', '\t', '\r', '\n', ',', ']', '}'];
+            let start = self.position;
+            while self.position < self.length && !STOP_CHARS.contains(&self.character) {
+                self.goto_next_char();
+            }
+            match &self.json[start..self.position] {
+                "true" => Ok(EsJson::Bool(true)),
+                "false" => Ok(EsJson::Bool(false)),
+                "null" => Ok(EsJson::Null),
+                literal if is_json_number(literal) => Ok(EsJson::Number(literal.to_string())),
+                _ => Err(Error::Parse { position: Some(start), detail: String::from("malformed json") }),
+            }
+        }
+
+        /// Read a json string.
+        fn read_json_string(&mut self) -> Result<String, Error> {
+            if self.character != '\"' {
+                return Err(self.malformed());
+            }
+            let start = self.position;
+            let mut end = None;
+            while self.goto_next_char() {
+                if self.character == '\"' && self.count_prev_char(b'\\').is_multiple_of(2) {
+                    end = Some(self.position + 1);
+                    self.goto_next_char();
+                    break;
+                }
+            }
+            let string = match end {
+                Some(end) => self.json[start..end].to_string(),
+                None => return Err(self.malformed()),
+            };
+            // strings are kept raw, but their escape sequences must be decodable.
+            if string.contains('\\') && unescape_json_string(&string[1..string.len() - 1]).is_err() {
+                return Err(self.malformed());
+            }
+            Ok(string)
+        }
+
+        /// Deserialize json string to EsJson.
+        fn from_json(json: &str) -> Result<EsJson, Error> {
+            Self::from_json_with_depth(json, DEFAULT_MAX_JSON_DEPTH)
+        }
+
+        /// Deserialize json string to EsJson, with nesting up to `max_depth` arrays and objects. 
+        fn from_json_with_depth(json: &str, max_depth: usize) -> Result<EsJson, Error> {
+            if json.trim().is_empty() {
+                return Err(Error::Parse { position: None, detail: String::from("json can not be empty.") });
+            }
+            let mut analyzer = EsJsonAnalyzer::new(json)?;
+            analyzer.max_depth = max_depth;
+            analyzer.skip_space();
+            let value = analyzer.read_json_value()?;
+            analyzer.skip_space();
+            if analyzer.position < analyzer.length {
+                return Err(Error::Parse { position: Some(analyzer.position), detail: String::from("unexpected characters after json value") });
+            }
+            Ok(value)
+        }
+
+        /// Check that a string is a single well-formed json value. 
+        #[cfg(test)]
+        fn is_valid(json: &str) -> bool {
+            Self::from_json(json).is_ok()
+        }
+
+        /// Serialize EsJson to json string. 
+        /// Keys and strings are decoded and escaped again, so the output is valid json independent of how the input was written. 
+        fn to_json(obj: &EsJson) -> String {
+            let mut json = String::new();
+            Self::write_json(obj, &mut json);
+            json
+        }
+
+        /// Write a value, with an explicit stack of the pieces left to write instead of recursion. 
+        fn write_json(obj: &EsJson, json: &mut String) {
+            enum Piece<'a> {
+                Value(&'a EsJson),
+                Key(&'a str),
+                Text(&'static str),
+            }
+            let mut pieces = vec![Piece::Value(obj)];
+            while let Some(piece) = pieces.pop() {
+                let value = match piece {
+                    Piece::Value(value) => value,
+                    Piece::Key(key) => {
+                        Self::write_raw_json_string(key, json);
+                        json.push(':');
+                        continue;
+                    }
+                    Piece::Text(text) => {
+                        json.push_str(text);
+                        continue;
+                    }
+                };
+                match value {
+                    EsJson::Array(ary) => {
+                        json.push('[');
+                        pieces.push(Piece::Text("]"));
+                        for (i, item) in ary.iter().enumerate().rev() {
+                            pieces.push(Piece::Value(item));
+                            if i > 0 {
+                                pieces.push(Piece::Text(","));
+                            }
+                        }
+                    }
+                    EsJson::Object(obj) => {
+                        json.push('{');
+                        pieces.push(Piece::Text("}"));
+                        for (i, (key, value)) in obj.iter().enumerate().rev() {
+                            pieces.push(Piece::Value(value));
+                            pieces.push(Piece::Key(key));
+                            if i > 0 {
+                                pieces.push(Piece::Text(","));
+                            }
+                        }
+                    }
+                    EsJson::String(str) => Self::write_raw_json_string(str, json),
+                    EsJson::Number(number) => json.push_str(number),
+                    EsJson::Bool(b) => json.push_str(if *b { "true" } else { "false" }),
+                    EsJson::Null => json.push_str("null"),
+                }
+            }
+        }
+
+        /// Write a raw json string as a quoted and escaped json string.
+        fn write_raw_json_string(raw: &str, json: &mut String) {
+            let content = raw.strip_prefix('\"').and_then(|s| s.strip_suffix('\"')).unwrap_or(raw);
+            let decoded = unescape_json_string(content).unwrap_or_else(|_| content.to_string());
+            write_json_string(&decoded, json);
+        }
+    }
+
+    /// Client on a local node for the examples of the documentation, not part of the api. 
+    #[doc(hidden)]
+    pub fn doc_client() -> Client {
+        Client::new(Transport::single_node("http://localhost:9200").expect("valid node url"))
+    }
+
+    /// Internals used by the benchmarks under `benches/`, not part of the api. Requires the `bench` feature. 
+    #[cfg(feature = "bench")]
+    #[doc(hidden)]
+    pub mod bench {
+        use super::RangeQuery;
+
+        /// The restricted queries of batches continuing after each of the `values`, like `Client::search` builds them. 
+        pub fn cmp_queries(query: &str, sort: &str, values: &[i64]) -> Vec<String> {
+            let range_query = RangeQuery::new(query, sort);
+            values.iter().map(|value| range_query.cmp("gt", *value)).collect()
+        }
+
+        /// The restricted queries of binary search steps between each pair of the `bounds`. 
+        pub fn range_queries(query: &str, sort: &str, bounds: &[(i64, i64)]) -> Vec<String> {
+            let range_query = RangeQuery::new(query, sort);
+            bounds.iter().map(|(start, end)| range_query.range(*start, *end)).collect()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// A request received by the test server. 
+        #[derive(Clone, Debug)]
+        struct Request {
+            method: String,
+            path: String,
+            headers: Vec<(String, String)>,
+            body: String,
+        }
+
+        impl Request {
+            /// The values of a header, the names are compared case-insensitively. 
+            fn header(&self, name: &str) -> Vec<&str> {
+                self.headers.iter().filter(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str()).collect()
+            }
+        }
+
+        /// Response of the test server, None never answers the request. 
+        type Handler = std::sync::Arc<dyn Fn(&Request) -> Option<(u16, String)> + Send + Sync>;
+
+        /// Http server on a local port answering each request with the handler, and recording the requests. 
+        struct Server {
+            url: String,
+            requests: std::sync::Arc<std::sync::Mutex<Vec<Request>>>,
+        }
+
+        impl Server {
+            async fn start(handler: impl Fn(&Request) -> Option<(u16, String)> + Send + Sync + 'static) -> Server {
+                use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+
+                let handler: Handler = std::sync::Arc::new(handler);
+                let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+                let url = format!("http://{}", listener.local_addr().unwrap());
+                let requests = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+                let recorded = requests.clone();
+                tokio::spawn(async move {
+                    while let Ok((stream, _)) = listener.accept().await {
+                        let handler = handler.clone();
+                        let recorded = recorded.clone();
+                        tokio::spawn(async move {
+                            let mut stream = tokio::io::BufReader::new(stream);
+                            let mut line = String::new();
+                            stream.read_line(&mut line).await?;
+                            let mut parts = line.split_whitespace();
+                            let method = parts.next().unwrap_or_default().to_string();
+                            let path = parts.next().unwrap_or_default().to_string();
+                            let mut headers = vec![];
+                            loop {
+                                line.clear();
+                                stream.read_line(&mut line).await?;
+                                match line.trim_end().split_once(':') {
+                                    Some((key, value)) => headers.push((key.to_string(), value.trim().to_string())),
+                                    None => break,
+                                }
+                            }
+                            let length = headers.iter().find(|(key, _)| key.eq_ignore_ascii_case("content-length")).map_or(0, |(_, value)| value.parse().unwrap());
+                            let mut body = vec![0; length];
+                            stream.read_exact(&mut body).await?;
+                            let request = Request { method, path, headers, body: String::from_utf8(body).unwrap() };
+                            recorded.lock().unwrap().push(request.clone());
+                            let (status, body) = match handler(&request) {
+                                Some(resp) => resp,
+                                None => std::future::pending().await,
+                            };
+                            let resp = format!("HTTP/1.1 {} X\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}", status, body.len(), body);
+                            stream.get_mut().write_all(resp.as_bytes()).await?;
+                            stream.get_mut().shutdown().await
+                        });
+                    }
+                });
+                Server { url, requests }
+            }
+
+            /// Start a server of an index, see `FakeEs`. 
+            async fn with(es: FakeEs) -> Server {
+                Self::start(move |request| Some(es.handle(request))).await
             }
-            if sort.is_empty() {
-                return Err(Error::Message(String::from("sort can not be empty.")));
+
+            fn client(&self) -> ClientBuilder {
+                Client::builder().transport(Transport::single_node(&self.url).unwrap())
             }
-            if from < 0 || size < 0 {
-                return Err(Error::Message(String::from("from and size can not be negative.")));
+
+            fn requests(&self) -> Vec<Request> {
+                self.requests.lock().unwrap().clone()
             }
-            if size == 0 {
-                return Ok(vec![]);
+        }
+
+        /// Documents with an "id" field holding each number, ids "1", "2"... in that order. 
+        fn numbered(ids: impl IntoIterator<Item = i64>) -> Vec<(String, String)> {
+            ids.into_iter().enumerate().map(|(i, id)| ((i + 1).to_string(), format!("{{\"id\":{},\"name\":\"doc {}\"}}", id, id))).collect()
+        }
+
+        /// The "id" of the "_source" of each returned document. 
+        fn sort_ids(docs: &[String]) -> Vec<i64> {
+            docs.iter().map(|doc| json::parse_with_depth(doc, 1000).unwrap().get("_source").unwrap().get("id").unwrap().as_i64().unwrap()).collect()
+        }
+
+        /// In-memory index answering the search, count, field caps and point in time APIs the client uses, 
+        /// with the query clauses the client builds: match_all, bool, range, term, exists and ids. 
+        /// Documents sharing sort values are returned in the order they were added in both directions, like the doc ids of lucene. 
+        /// Searches of a point in time add the "_shard_doc" tie-breaker to the sort values, the position of the document. 
+        /// Requests beyond the max_result_window fail with 400 like elasticsearch. 
+        struct FakeEs {
+            docs: Vec<(String, EsJson)>,
+            max_result_window: i64,
+        }
+
+        impl FakeEs {
+            fn new(docs: Vec<(String, String)>) -> FakeEs {
+                let docs = docs.into_iter().map(|(id, source)| (id, json::parse_with_depth(&source, 1000).unwrap())).collect();
+                FakeEs { docs, max_result_window: DEFAULT_MAX_RESULT_WINDOW }
             }
-            
-            let query = if query == "" {"{\"match_all\":{}}"} else {query};
-            let mut asc = asc;
-            let mut from = from;
-            let mut size = size;
 
-            // When the queried data is near the end of the data set, reverse the query direction.
-            let mut reverse = false;
-            if from > MAX_FROM {
-                let total = self.count(index, query).await?;
-                if total == 0 || from > total {
-                    return Ok(vec![]);
+            fn window(mut self, max_result_window: i64) -> FakeEs {
+                self.max_result_window = max_result_window;
+                self
+            }
+
+            fn handle(&self, request: &Request) -> (u16, String) {
+                let (path, params) = request.path.split_once('?').unwrap_or((&request.path, ""));
+                let body = match request.body.is_empty() {
+                    true => EsJson::Object(JsonObject::default()),
+                    false => json::parse(&request.body).unwrap(),
+                };
+                let query = body.get("query");
+                let matches = |id: &str, source: &EsJson| query.is_none_or(|query| Self::matches(query, id, source));
+                if path.ends_with("/_count") {
+                    return (200, format!("{{\"count\":{},\"_shards\":{{\"total\":1,\"successful\":1,\"skipped\":0,\"failed\":0}}}}", self.docs.iter().filter(|(id, source)| matches(id, source)).count()));
                 }
-                reverse = from > (total - from);
-                if reverse {
-                    asc = !asc;
-                    let from2 = total - from - size;
-                    let size2 = if from2 < 0 {size + from2} else {size};
-                    from = from2.max(0);
-                    size = size2.max(0);
-                    if size == 0 {
-                        return Ok(vec![]);
+                if path.ends_with("/_field_caps") {
+                    let field = params.split('&').find_map(|param| param.strip_prefix("fields=")).unwrap_or_default();
+                    return (200, format!("{{\"indices\":[\"idx\"],\"fields\":{{\"{}\":{{\"long\":{{\"type\":\"long\",\"searchable\":true,\"aggregatable\":true}}}}}}}}", field));
+                }
+                if path.ends_with("/_pit") && request.method == "POST" {
+                    return (200, String::from("{\"id\":\"pit-0\"}"));
+                }
+                if path == "/_pit" {
+                    return (200, String::from("{\"succeeded\":true,\"num_freed\":1}"));
+                }
+                if !path.ends_with("/_search") {
+                    return (404, String::from("{\"error\":\"no handler\",\"status\":404}"));
+                }
+
+                let from = body.get("from").and_then(EsJson::as_i64).unwrap_or(0);
+                let size = body.get("size").and_then(EsJson::as_i64).unwrap_or(10);
+                if from + size > self.max_result_window {
+                    return (400, format!("{{\"error\":{{\"type\":\"illegal_argument_exception\",\"reason\":\"Result window is too large, from + size must be less than or equal to: [{}] but was [{}].\"}},\"status\":400}}", self.max_result_window, from + size));
+                }
+                let mut sort = Self::sort_keys(body.get("sort"));
+                if body.get("pit").is_some() && !sort.iter().any(|(field, _, _)| field == "_doc" || field == "_shard_doc") {
+                    sort.push((String::from("_shard_doc"), true, false));
+                }
+                let mut hits = self.docs.iter().enumerate().filter(|(_, (id, source))| matches(id, source))
+                    .filter(|(_, (id, source))| body.get("post_filter").is_none_or(|filter| Self::matches(filter, id, source)))
+                    .map(|(i, (id, source))| (id, source, sort.iter().map(|(field, _, _)| match field.as_str() {
+                        "_doc" | "_shard_doc" => EsJson::Number(i.to_string()),
+                        "_id" => EsJson::String(format!("\"{}\"", id)),
+                        field => source.find_path(field).map(Self::copy).unwrap_or(EsJson::Null),
+                    }).collect::<Vec<EsJson>>()))
+                    .collect::<Vec<_>>();
+                let order = |a: &[EsJson], b: &[EsJson]| {
+                    for ((_, asc, missing_first), (a, b)) in sort.iter().zip(a.iter().zip(b)) {
+                        let ordering = match (a, b) {
+                            (EsJson::Null, EsJson::Null) => std::cmp::Ordering::Equal,
+                            (EsJson::Null, _) => if *missing_first { std::cmp::Ordering::Less } else { std::cmp::Ordering::Greater },
+                            (_, EsJson::Null) => if *missing_first { std::cmp::Ordering::Greater } else { std::cmp::Ordering::Less },
+                            (a, b) if *asc => Self::compare(a, b),
+                            (a, b) => Self::compare(b, a),
+                        };
+                        if ordering.is_ne() {
+                            return ordering;
+                        }
                     }
+                    std::cmp::Ordering::Equal
+                };
+                hits.sort_by(|a, b| order(&a.2, &b.2));
+                if let Some(EsJson::Array(search_after)) = body.get("search_after") {
+                    hits.retain(|hit| order(&hit.2, search_after).is_gt());
                 }
+                let total = hits.len();
+                let total = match body.get("track_total_hits").and_then(EsJson::as_bool) == Some(true) || total <= 10000 {
+                    true => format!("{{\"value\":{},\"relation\":\"eq\"}}", total),
+                    false => String::from("{\"value\":10000,\"relation\":\"gte\"}"),
+                };
+                let source_filter = body.get("_source");
+                let hits = hits.iter().skip(from as usize).take(size as usize).map(|(id, source, values)| {
+                    let mut hit = format!("{{\"_index\":\"idx\",\"_id\":\"{}\",\"_score\":null", id);
+                    if let Some(source) = Self::filter_source(source, source_filter) {
+                        let _ = write!(hit, ",\"_source\":{}", source);
+                    }
+                    let values = values.iter().map(EsJsonAnalyzer::to_json).collect::<Vec<String>>();
+                    let _ = write!(hit, ",\"sort\":[{}]}}", values.join(","));
+                    hit
+                }).collect::<Vec<String>>();
+                let pit = body.get("pit").and_then(|pit| pit.get("id")).and_then(EsJson::as_raw_str).map(|id| format!("\"pit_id\":\"{}+\",", id)).unwrap_or_default();
+                (200, format!("{{{}\"took\":1,\"timed_out\":false,\"_shards\":{{\"total\":1,\"successful\":1,\"skipped\":0,\"failed\":0}},\"hits\":{{\"total\":{},\"max_score\":null,\"hits\":[{}]}}}}", pit, total, hits.join(",")))
             }
 
-            // When the from parameter is large, find a sort value that can exclude some of the from data, and reduce the from value.
-            let mut new_query = String::from(query);
-            let mut new_from = from;
-            if from > MAX_FROM {
-                let min_item = self.query(index, query, Some(&vec![sort]), sort, true, 0, 1).await?;
-                let min_item = min_item.get_hits()?.last();
-                let sort_min = match min_item {
-                    Some(item) => item.find_json("\"_source\"")?.find_json(&format!("\"{}\"", sort))?.get_string()?.parse::<i64>().unwrap(),
-                    None => return Ok(vec![]),
-                };
-                let max_item = self.query(index, query, Some(&vec![sort]), sort, false, 0, 1).await?;
-                let max_item = max_item.get_hits()?.last();
-                let sort_max = match max_item {
-                    Some(item) => item.find_json("\"_source\"")?.find_json(&format!("\"{}\"", sort))?.get_string()?.parse::<i64>().unwrap(),
-                    None => return Ok(vec![]),
+            fn copy(value: &EsJson) -> EsJson {
+                json::parse_with_depth(&EsJsonAnalyzer::to_json(value), 1000).unwrap()
+            }
+
+            /// The field, ascending and missing first flags of each sort key of a sort clause. 
+            fn sort_keys(sort: Option<&EsJson>) -> Vec<(String, bool, bool)> {
+                let keys = match sort {
+                    Some(EsJson::Array(keys)) => keys.iter().collect(),
+                    Some(key) => vec![key],
+                    None => vec![],
                 };
+                keys.into_iter().map(|key| match key {
+                    EsJson::String(_) => (key.as_raw_str().unwrap().to_string(), true, false),
+                    EsJson::Object(obj) => {
+                        let field = obj[0].0.trim_matches('"').to_string();
+                        let (order, missing) = match &obj[0].1 {
+                            EsJson::Object(_) => (obj[0].1.get("order").and_then(EsJson::as_raw_str).unwrap_or("asc"), obj[0].1.get("missing").and_then(EsJson::as_raw_str)),
+                            order => (order.as_raw_str().unwrap(), None),
+                        };
+                        (field, order == "asc", missing == Some("_first"))
+                    }
+                    _ => panic!("unsupported sort {}", key),
+                }).collect()
+            }
 
-                let new_start;
-                if asc {
-                    (new_start, new_from) = self.find_new_from(index, query, sort, sort_min, sort_max, from).await?;
-                    new_query = Self::build_cmp_query(query, sort, "gt", new_start);
-                } else {
-                    (new_start, new_from) = self.find_new_from(index, query, sort, sort_max, sort_min, from).await?;
-                    new_query = Self::build_cmp_query(query, sort, "lt", new_start)
+            fn compare(a: &EsJson, b: &EsJson) -> std::cmp::Ordering {
+                match (a, b) {
+                    (EsJson::Number(a), EsJson::Number(b)) => match (a.parse::<i128>(), b.parse::<i128>()) {
+                        (Ok(a), Ok(b)) => a.cmp(&b),
+                        _ => a.parse::<f64>().unwrap().total_cmp(&b.parse::<f64>().unwrap()),
+                    },
+                    (EsJson::String(_), EsJson::Number(n)) | (EsJson::Number(n), EsJson::String(_)) => {
+                        let (a, b) = if matches!(a, EsJson::Number(_)) { (n.to_string(), b.as_raw_str().unwrap().to_string()) } else { (a.as_raw_str().unwrap().to_string(), n.to_string()) };
+                        Self::compare(&EsJson::Number(a), &EsJson::Number(b))
+                    }
+                    (a, b) => a.get_unescaped().unwrap().cmp(&b.get_unescaped().unwrap()),
                 }
             }
 
-            let mut remain_size = size;
-            let mut retrieve_size = size.min(MAX_SIZE);
-            let mut batch = self.query(index, &new_query, source, sort, asc, new_from, retrieve_size).await?;
-            let mut hits = batch.get_hits()?;
-            if hits.len() == 0 {
-                return Ok(vec![]);
-            }
-            let mut list = vec![];
-            list.extend(hits.iter().map(|item| EsJsonAnalyzer::to_json(item)));
-            remain_size -= hits.len() as i64;
-            while remain_size > 0 {
-                let last_item = hits.last().unwrap();
-                let last_sort = last_item.find_json("\"sort\"")?.get_array()?.first().unwrap().get_string()?.parse::<i64>().unwrap();
-                if asc {
-                    new_query = Self::build_cmp_query(query, sort, "gt", last_sort);
-                } else {
-                    new_query = Self::build_cmp_query(query, sort, "lt", last_sort);
+            /// The clauses of a bool occurrence, either a single clause or an array. 
+            fn clauses(value: Option<&EsJson>) -> Vec<&EsJson> {
+                match value {
+                    Some(EsJson::Array(clauses)) => clauses.iter().collect(),
+                    Some(clause) => vec![clause],
+                    None => vec![],
                 }
-                retrieve_size = remain_size.min(MAX_SIZE);
-                batch = self.query(index, &new_query, source, sort, asc, 0, retrieve_size).await?;
-                hits = batch.get_hits()?;
-                if hits.len() == 0 {
-                    break;
+            }
+
+            fn matches(query: &EsJson, id: &str, source: &EsJson) -> bool {
+                let (kind, clause) = match query {
+                    EsJson::Object(obj) if obj.len() == 1 => (obj[0].0.trim_matches('"'), &obj[0].1),
+                    _ => panic!("unsupported query {}", query),
+                };
+                let field = |clause: &EsJson| match clause {
+                    EsJson::Object(obj) => (obj[0].0.trim_matches('"').to_string(), Self::copy(&obj[0].1)),
+                    _ => panic!("unsupported query {}", query),
+                };
+                match kind {
+                    "match_all" => true,
+                    "bool" => {
+                        let should = Self::clauses(clause.get("should"));
+                        Self::clauses(clause.get("must")).iter().all(|q| Self::matches(q, id, source))
+                            && Self::clauses(clause.get("filter")).iter().all(|q| Self::matches(q, id, source))
+                            && !Self::clauses(clause.get("must_not")).iter().any(|q| Self::matches(q, id, source))
+                            && (should.is_empty() || should.iter().any(|q| Self::matches(q, id, source)))
+                    }
+                    "range" => {
+                        let (name, bounds) = field(clause);
+                        let value = match source.find_path(&name) {
+                            Ok(value @ (EsJson::Number(_) | EsJson::String(_))) => value,
+                            _ => return false,
+                        };
+                        bounds.get_object().unwrap().iter().all(|(cmp, bound)| {
+                            let ordering = Self::compare(value, bound);
+                            match cmp.trim_matches('"') {
+                                "gt" => ordering.is_gt(),
+                                "gte" => ordering.is_ge(),
+                                "lt" => ordering.is_lt(),
+                                "lte" => ordering.is_le(),
+                                cmp => panic!("unsupported range {}", cmp),
+                            }
+                        })
+                    }
+                    "term" => {
+                        let (name, term) = field(clause);
+                        let term = term.get("value").map(Self::copy).unwrap_or(term);
+                        source.find_path(&name).is_ok_and(|value| Self::compare(value, &term).is_eq())
+                    }
+                    "ids" => clause.get("values").unwrap().get_array().unwrap().iter().any(|value| value.as_raw_str() == Some(id)),
+                    "exists" => source.find_path(clause.get("field").unwrap().as_raw_str().unwrap()).is_ok_and(|value| !value.is_null()),
+                    kind => panic!("unsupported query {}", kind),
                 }
-                list.extend(hits.iter().map(|item| EsJsonAnalyzer::to_json(item)));
-                remain_size -= hits.len() as i64;
             }
 
-            // If result is reverse query data, reverse it back.
-            if reverse {
-                list.reverse();
+            /// The "_source" of a hit filtered by the source filter of the request, None when it is disabled. 
+            fn filter_source(source: &EsJson, filter: Option<&EsJson>) -> Option<String> {
+                let (includes, excludes) = match filter {
+                    None => (None, vec![]),
+                    Some(EsJson::Bool(false)) => return None,
+                    Some(EsJson::Array(includes)) => (Some(includes.iter().collect::<Vec<&EsJson>>()), vec![]),
+                    Some(filter) => (filter.get("includes").map(|i| i.get_array().unwrap().iter().collect()), filter.get("excludes").map_or(vec![], |e| e.get_array().unwrap().iter().collect())),
+                };
+                let selected = |patterns: &[&EsJson], key: &str| patterns.iter().map(|p| p.as_raw_str().unwrap()).any(|p| p == "*" || p == key || p.starts_with(&format!("{}.", key)) || p.strip_suffix(".*") == Some(key));
+                let fields = source.get_object().unwrap().iter()
+                    .filter(|(key, _)| includes.as_ref().is_none_or(|includes| selected(includes, key.trim_matches('"'))))
+                    .filter(|(key, _)| !excludes.iter().any(|p| p.as_raw_str() == Some(key.trim_matches('"'))))
+                    .map(|(key, value)| format!("{}:{}", key, EsJsonAnalyzer::to_json(value)))
+                    .collect::<Vec<String>>();
+                Some(format!("{{{}}}", fields.join(",")))
             }
+        }
 
-            Ok(list)
+        /// Client of a fake cluster with a small max_from and batch size, so a few hundred documents need deep paging. 
+        /// Use it with `FakeEs::window(100)`, the fake cluster then rejects requests beyond the window of the client. 
+        fn small_client(server: &Server) -> Client {
+            server.client().max_from(10).max_size(15).max_result_window(100).build().unwrap()
         }
 
-        /// Call elasticsearch's searchAPI to get the documents that meet the conditions. 
-        async fn query(&self, index: &str, query: &str, source: Option<&Vec<&str>>, sort: &str, asc: bool, from: i64, size: i64) -> Result<EsJson, Error> {
+        #[test]
+        fn range_query_text() {
+            let query = "{\"bool\":{\"must\":[{\"term\":{\"tag\":\"a}}\"}}]}}";
+            let range_query = RangeQuery::new(query, "meta.seq");
+            assert_eq!(range_query.cmp("gt", -5i64), format!("{{\"bool\":{{\"must\":{},\"filter\":{{\"range\":{{\"meta.seq\":{{\"gt\":-5}}}}}}}}}}", query));
+            assert_eq!(range_query.cmp("lt", u64::MAX), format!("{{\"bool\":{{\"must\":{},\"filter\":{{\"range\":{{\"meta.seq\":{{\"lt\":{}}}}}}}}}}}", query, u64::MAX));
+            assert_eq!(range_query.range(i64::MIN, 7i64), format!("{{\"bool\":{{\"must\":{},\"filter\":{{\"range\":{{\"meta.seq\":{{\"gte\":{},\"lte\":7}}}}}}}}}}", query, i64::MIN));
+            assert_eq!(range_query.range(0.5f64, 1.5f64), format!("{{\"bool\":{{\"must\":{},\"filter\":{{\"range\":{{\"meta.seq\":{{\"gte\":0.5,\"lte\":1.5}}}}}}}}}}", query));
+            assert_eq!(Client::build_cmp_query(query, "meta.seq", "gt", 3i64), range_query.cmp("gt", 3i64));
+            assert!(json::parse(&range_query.range(1i64, 2i64)).is_ok());
+        }
 
-            let url = format!("{}/_search", index);
+        #[test]
+        fn empty_json() {
+            for json in ["", " ", "\n\t\r "] {
+                assert!(matches!(json::parse(json), Err(Error::Parse { .. })), "{:?}", json);
+                assert!(!EsJsonAnalyzer::is_valid(json));
+            }
+        }
 
-            let mut query_builder = String::new();
-            query_builder.push_str("{");
-            query_builder.push_str(&format!("\"query\":{},", query));
-            query_builder.push_str(&format!("\"sort\":{{\"{}\":\"{}\"}},", sort, if asc { "asc" } else { "desc"}));
-            match source {
-                Some(source) => {
-                    let source_str = source.iter().map(|s|format!("\"{}\"", s)).collect::<Vec<String>>().join(",");
-                    query_builder.push_str(&format!("\"_source\": [{}],", source_str));
-                },
-                None => {},
+        #[tokio::test]
+        async fn empty_response() {
+            let server = Server::start(|_| Some((200, String::new()))).await;
+            let client = server.client().build().unwrap();
+            assert!(matches!(client.count("idx", "").await, Err(Error::Parse { .. })));
+            assert!(matches!(client.search("idx", "", None, "id", true, 0, 10).await, Err(Error::Parse { .. })));
+        }
+
+        #[test]
+        fn malformed_json() {
+            for json in ["{\"a\":1", "{\"a\":", "{\"a\"", "{\"a", "[1,2", "[", "{", "\"abc", "{\"a\" 1}", "}{", "@@", "[1 2]", "[1,]", "[,1]", "{,}", "{\"a\":1,}", "{\"a\":1 \"b\":2}", "{\"a\":\"\\x\"}", "{1:2}"] {
+                assert!(matches!(json::parse(json), Err(Error::Parse { .. })), "{:?}", json);
+                assert!(!EsJsonAnalyzer::is_valid(json), "{:?}", json);
             }
-            query_builder.push_str(&format!("\"from\":{},", from));
-            query_builder.push_str(&format!("\"size\":{} }}", size));
+            match json::parse("{\"a\":[1,2,x]}") {
+                Err(Error::Parse { position, .. }) => assert_eq!(position, Some(10)),
+                _ => panic!("garbage accepted"),
+            }
+        }
 
-            let body = query_builder;
-            let resp = self.post(&url, &body).await?;
-            let json = EsJsonAnalyzer::from_json(&resp);
+        #[test]
+        fn unicode_strings() {
+            let emoji = json::parse("\"😀\"").unwrap();
+            assert_eq!(emoji.get_unescaped().unwrap(), "😀");
+            let pair = json::parse("\"\\uD83D\\uDE00\"").unwrap();
+            assert_eq!(pair.get_unescaped().unwrap(), "😀");
+            assert_eq!(EsJsonAnalyzer::to_json(&pair), "\"😀\"");
+            let mixed = json::parse("{\"name\":\"caf\\u00e9 café \\u4e2d文 \\ud83d\\ude00😀\"}").unwrap();
+            assert_eq!(mixed.find_json("\"name\"").unwrap().get_unescaped().unwrap(), "café café 中文 😀😀");
+            for json in ["\"\\uD83D\"", "\"\\uD83Dx\"", "\"\\uD83D\\u0041\"", "\"\\u12\""] {
+                assert!(json::parse(json).is_err(), "{:?}", json);
+            }
+        }
 
-            Ok(json)
+        /// Check that two values hold the same decoded data. 
+        fn same_json(a: &EsJson, b: &EsJson) -> bool {
+            match (a, b) {
+                (EsJson::Array(a), EsJson::Array(b)) => a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_json(a, b)),
+                (EsJson::Object(a), EsJson::Object(b)) => a.len() == b.len() && a.iter().zip(b).all(|((ka, va), (kb, vb))| {
+                    unescape_json_string(ka.trim_matches('"')).unwrap() == unescape_json_string(kb.trim_matches('"')).unwrap() && same_json(va, vb)
+                }),
+                (EsJson::String(_), EsJson::String(_)) => a.get_unescaped().unwrap() == b.get_unescaped().unwrap(),
+                (EsJson::Number(a), EsJson::Number(b)) => a == b,
+                (EsJson::Bool(a), EsJson::Bool(b)) => a == b,
+                (EsJson::Null, EsJson::Null) => true,
+                _ => false,
+            }
         }
 
-        /// Use binary search to find new query parameters with the same result as the original query but with a smaller from value. 
-        async fn find_new_from(&self, index: &str, query: &str, sort: &str, sort_start: i64, sort_end: i64, from: i64) -> Result<(i64, i64), Error> {
-            let mut new_start = sort_start;
-            let mut new_end = sort_end;
-            let mut new_from: i64;
-            loop {
-                let sort_min = new_start.min(new_end);
-                let sort_abs = (new_end - new_start).abs();
-                if sort_abs <= 1 {
-                    return Ok((sort_min, sort_abs));
-                }
-                let sort_mid = sort_min + sort_abs / 2;
+        #[test]
+        fn json_round_trip() {
+            let input = " { \"a\\u0062\" : [ 1, -2.5e3, true, false, null, { } , [ ] ], \"s\\\"q\" : \"line\\nbreak \\\\ \\/ \\t\\u0001 \\ud83d\\ude00\", \"n\":{\"deep\":{\"x\":\"\"}}, \"a\\u0062\": 0 } ";
+            let first = json::parse(input).unwrap();
+            let text = EsJsonAnalyzer::to_json(&first);
+            assert!(EsJsonAnalyzer::is_valid(&text));
+            let second = json::parse(&text).unwrap();
+            assert!(same_json(&first, &second));
+            assert_eq!(EsJsonAnalyzer::to_json(&second), text);
+            assert_eq!(text, "{\"ab\":[1,-2.5e3,true,false,null,{},[]],\"s\\\"q\":\"line\\nbreak \\\\ / \\t\\u0001 😀\",\"n\":{\"deep\":{\"x\":\"\"}},\"ab\":0}");
+        }
 
-                let mid_query: String;
-                if sort_start < sort_end {
-                    mid_query = Self::build_range_query(query, sort, sort_start, sort_mid);
-                } else {
-                    mid_query = Self::build_range_query(query, sort, sort_mid, sort_start);
-                }
-                let mid_count = self.count(index, &mid_query).await?;
-                new_from = from - mid_count;
+        #[test]
+        fn string_at_first_byte() {
+            assert_eq!(json::parse("\"abc\"").unwrap().get_unescaped().unwrap(), "abc");
+            assert_eq!(json::parse("\"\"").unwrap().get_unescaped().unwrap(), "");
+            assert_eq!(json::parse("\"\\\\\"").unwrap().get_unescaped().unwrap(), "\\");
+            assert_eq!(json::parse("\"\\\"\"").unwrap().get_unescaped().unwrap(), "\"");
+            assert_eq!(json::parse("\"\\\\\\\\\\\"\"").unwrap().get_unescaped().unwrap(), "\\\\\"");
+            assert!(json::parse("\"\\\"").is_err());
+        }
 
-                if new_from < 0 {
-                    new_end = sort_mid;
-                } else {
-                    new_start = sort_mid;
-                    if new_from <= MAX_FROM {
-                        break;
-                    }
+        #[test]
+        fn json_module_accessors() {
+            let value = json::parse(" {\"caf\\u00e9\":\"a\\\"b\\u00e9\",\"id\":12,\"n\":[1.5,true,null]} \n").unwrap();
+            let name = value.get("café").unwrap();
+            assert_eq!(name.as_str().as_deref(), Some("a\"bé"));
+            assert!(matches!(name.as_str(), Some(std::borrow::Cow::Owned(_))));
+            assert_eq!(name.as_raw_str(), Some("a\\\"b\\u00e9"));
+            assert_eq!(value.get("id").and_then(EsJson::as_i64), Some(12));
+            assert_eq!(value.get("id").and_then(|id| id.as_str()), None);
+            let items = value.get("n").unwrap();
+            assert_eq!(items.index(0).and_then(EsJson::as_f64), Some(1.5));
+            assert_eq!(items.index(1).and_then(EsJson::as_bool), Some(true));
+            assert!(items.index(2).unwrap().is_null());
+            assert!(items.index(3).is_none());
+            assert!(matches!(json::parse("\"plain\"").unwrap().as_str(), Some(std::borrow::Cow::Borrowed("plain"))));
+            assert_eq!(json::parse("\"plain\"").unwrap().into_string().as_deref(), Some("plain"));
+            assert_eq!(value.get("id").map(|id| id.to_string()), Some(String::from("12")));
+            let pairs = json::parse("{\"a\":[1,{\"b\":\"x\\ty\"}],\"a\":null}").unwrap().into_object().unwrap();
+            assert_eq!(pairs.iter().map(|(key, _)| key.as_str()).collect::<Vec<&str>>(), ["\"a\"", "\"a\""]);
+            let mut items = pairs.into_iter().next().unwrap().1.into_array().unwrap();
+            assert_eq!(items.pop().unwrap().into_object().unwrap().pop().unwrap().1.into_string().as_deref(), Some("x\ty"));
+            assert!(items.pop().unwrap().into_string().is_none());
+            assert!(json::parse("1").unwrap().into_array().is_none());
+            assert!(json::parse("[]").unwrap().into_object().is_none());
+            for json in ["{\"a\":1}}", "{\"a\":1} x", "[1]]", "1 2", "\"a\"\"b\"", "null,"] {
+                assert!(matches!(json::parse(json), Err(Error::Parse { position: Some(_), .. })), "{:?}", json);
+            }
+        }
+
+        #[test]
+        fn object_key_index() {
+            for width in [3, OBJECT_INDEX_MIN_KEYS, 200] {
+                let fields = (0..width).map(|i| format!("\"k{}\":{}", i, i)).collect::<Vec<String>>().join(",");
+                let value = json::parse(&format!("{{{},\"k1\":-1,\"caf\\u00e9\":1,\"café\":2}}", fields)).unwrap();
+                let EsJson::Object(obj) = &value else { panic!("not an object") };
+                assert_eq!(obj.index.is_some(), width + 3 >= OBJECT_INDEX_MIN_KEYS);
+                for i in 0..width {
+                    assert_eq!(value.get(&format!("k{}", i)).and_then(EsJson::as_i64), Some(i as i64));
+                    assert_eq!(value.find_json(&format!("\"k{}\"", i)).unwrap().get_i64().unwrap(), i as i64);
                 }
+                assert_eq!(value.get_all("k1").filter_map(EsJson::as_i64).collect::<Vec<i64>>(), [1, -1]);
+                assert_eq!(value.get_all("café").filter_map(EsJson::as_i64).collect::<Vec<i64>>(), [1, 2]);
+                assert_eq!(value.get("café").and_then(EsJson::as_i64), Some(1));
+                assert!(value.get("missing").is_none());
+                assert_eq!(value.get_all("missing").count(), 0);
+                assert!(value.find_optional_json("\"missing\"").unwrap().is_none());
             }
 
-            Ok((new_start, new_from))
+            let mut value = json::parse(&format!("{{{}}}", (0..20).map(|i| format!("\"k{}\":{}", i, i)).collect::<Vec<String>>().join(","))).unwrap();
+            value.fill_hits();
+            assert!(value.get_hits().unwrap().is_empty());
+            assert!(value.get("k19").is_some());
         }
 
-        /// Add range restrictions to the original query. 
-        fn build_range_query(query: &str, sort: &str, start: i64, end: i64) -> String {
-            format!("{{\"bool\":{{\"must\":{},\"filter\":{{\"range\":{{\"{}\":{{\"gte\":{},\"lte\":{}}}}}}}}}}}", query, sort, start, end)
+        #[test]
+        fn deeply_nested_json() {
+            let too_deep = |result: Result<EsJson, Error>| matches!(result, Err(Error::Parse { position: Some(_), detail }) if detail == "json nesting too deep");
+            let arrays = format!("{}{}", "[".repeat(10_000), "]".repeat(10_000));
+            let objects = format!("{}1{}", "{\"a\":".repeat(10_000), "}".repeat(10_000));
+            assert!(too_deep(json::parse(&arrays)));
+            assert!(too_deep(json::parse(&objects)));
+            assert!(too_deep(json::parse(&arrays[..5_000])));
+            assert!(!EsJsonAnalyzer::is_valid(&arrays));
+            assert!(json::parse(&format!("{}{}", "[".repeat(256), "]".repeat(256))).is_ok());
+            assert!(too_deep(json::parse(&format!("{}{}", "[".repeat(257), "]".repeat(257)))));
+            for json in [&arrays, &objects] {
+                let value = json::parse_with_depth(json, 10_000).unwrap();
+                assert_eq!(&EsJsonAnalyzer::to_json(&value), json);
+            }
+            assert!(too_deep(json::parse_with_depth(&objects, 9_999)));
         }
 
-        /// Add range restrictions to the original query. 
-        fn build_cmp_query(query: &str, sort: &str, cmp: &str, value: i64) -> String {
-            format!("{{\"bool\":{{\"must\":{},\"filter\":{{\"range\":{{\"{}\":{{\"{}\":{}}}}}}}}}}}", query, sort, cmp, value)
+        #[tokio::test]
+        async fn max_json_depth() {
+            let source = format!("{}1{}", "{\"a\":".repeat(300), "}".repeat(300));
+            let server = Server::with(FakeEs::new(vec![(String::from("1"), format!("{{\"id\":1,\"blob\":{}}}", source))])).await;
+            let result = server.client().build().unwrap().search("idx", "", None, "id", true, 0, 10).await;
+            assert!(matches!(result, Err(Error::Parse { position: Some(_), detail }) if detail == "batch #1: json nesting too deep"));
+            let docs = server.client().max_json_depth(400).build().unwrap().search("idx", "", None, "id", true, 0, 10).await.unwrap();
+            assert_eq!(sort_ids(&docs), vec![1]);
         }
 
-        /// Call elasticsearch's countAPI to get the total number of documents that meet query conditions. 
-        async fn count(&self, index: &str, query: &str) -> Result<i64, Error> {
-            let url = format!("{}/_count", index);
-            let body = format!("{{\"query\": {}}}", query);
-            let resp = self.post(&url, &body).await?;
-            let json = EsJsonAnalyzer::from_json(&resp);
-            let value = json.find_json("\"count\"")?.get_string()?;
-            match value.parse::<i64>() {
-                Ok(count) => Ok(count),
-                Err(e) => Err(Error::Message(format!("Parse error: {}", e))),
+        #[test]
+        fn json_literals() {
+            for number in ["0", "-0", "12", "-12.5", "1.5e10", "1.0E-5", "2e+3"] {
+                assert!(is_json_number(number), "{}", number);
+                assert_eq!(json::parse(number).unwrap().get_number().unwrap(), number);
             }
+            for literal in ["NaN", "-NaN", "Infinity", "-", "01", "1.", ".5", "1e", "+1", "0x10", "True", "nul"] {
+                assert!(!is_json_number(literal), "{}", literal);
+                assert!(json::parse(literal).is_err(), "{}", literal);
+            }
+            assert!(matches!(json::parse("true"), Ok(EsJson::Bool(true))));
+            assert!(matches!(json::parse("false"), Ok(EsJson::Bool(false))));
+            assert!(matches!(json::parse("null"), Ok(EsJson::Null)));
+            assert!(json::parse("[1,NaN]").is_err());
         }
 
-        /// Call elasticsearch low level rest client, post json to elasticsearch cluster. 
-        async fn post(&self, url: &str, body: &str) -> Result<String, Error> {
+        /// The documents of a window of `from` and `size` within `total` documents numbered from 0. 
+        fn window_model(total: i64, from: i64, size: i64) -> Vec<i64> {
+            (0..total).skip(from as usize).take(size as usize).collect()
+        }
 
-            let resp = self.0
-                .send(
-                    elasticsearch::http::Method::Post,
-                    url,
-                    HeaderMap::new(),
-                    Option::<&str>::None,
-                    Some(body),
-                    None,
-                ).await;
+        /// The same window read through the mirrored window of the reversed documents. 
+        fn reverse_window_model(total: i64, from: i64, size: i64) -> Vec<i64> {
+            let (reverse_from, reverse_size) = Client::reverse_window(total, from, size).unwrap();
+            let mut docs = (0..total).rev().skip(reverse_from as usize).take(reverse_size as usize).collect::<Vec<i64>>();
+            docs.reverse();
+            docs
+        }
 
-            match resp {
-                Ok(resp) if resp.status_code() != 200  => {
-                    let err = String::from_utf8(resp.bytes().await.unwrap().to_vec()).unwrap_or_default();
-                    Err(Error::Message(err))
-                }
-                Err(e) => {
-                    Err(Error::Message(format!("{}", e)))
-                }
-                _ => {
-                    Ok(String::from_utf8(resp.unwrap().bytes().await.unwrap().to_vec()).unwrap_or_default())
-                }
-            }
+        #[test]
+        fn reverse_window_edges() {
+            // from == total
+            assert_eq!(Client::reverse_window(100, 100, 10).unwrap(), (0, 0));
+            // from == total - 1
+            assert_eq!(Client::reverse_window(100, 99, 10).unwrap(), (0, 1));
+            assert_eq!(reverse_window_model(100, 99, 10), vec![99]);
+            // the window spans the midpoint
+            assert_eq!(Client::reverse_window(100, 40, 20).unwrap(), (40, 20));
+            assert_eq!(reverse_window_model(100, 45, 30), window_model(100, 45, 30));
+            assert_eq!(Client::reverse_window(i64::MAX, i64::MAX, i64::MAX).unwrap(), (0, 0));
+            assert!(Client::reverse_window(-2, i64::MAX, 10).is_err());
         }
-    }
 
-    /// json struct
-    enum EsJson {
-        Array(Vec<EsJson>),
-        Object(Vec<(String, EsJson)>),
-        String(String),
-    }
+        proptest::proptest! {
+            #[test]
+            fn reverse_window_matches_forward(total in 0..300i64, from in 0..320i64, size in 0..320i64) {
+                proptest::prop_assert_eq!(reverse_window_model(total, from, size), window_model(total, from, size));
+            }
 
-    /// json struct view functions
-    impl EsJson {
-        fn get_array(&self) -> Result<&Vec<EsJson>, Error> {
-            match self {
-                EsJson::Array(arr) => Ok(arr),
-                _ => Err(Error::Message(String::from("invalid json"))),
+            #[test]
+            fn reverse_window_near_end(total in 1..300i64, back in 0..2i64, size in 0..320i64) {
+                let from = total - back;
+                proptest::prop_assert_eq!(reverse_window_model(total, from, size), window_model(total, from, size));
             }
         }
 
-        fn get_object(&self) -> Result<&Vec<(String, EsJson)>, Error> {
-            match self {
-                EsJson::Object(obj) => Ok(obj),
-                _ => Err(Error::Message(String::from("invalid json"))),
+        #[tokio::test]
+        async fn reversed_search_windows() {
+            let server = Server::with(FakeEs::new(numbered((1..=300).map(|id| id * 3))).window(100)).await;
+            let client = small_client(&server);
+            assert!(client.explain_plan("idx", "", "id", true, 290, 10).await.unwrap().reverse);
+            for (from, size) in [(300, 10), (299, 10), (290, 10), (140, 30), (160, 200), (250, 60)] {
+                for asc in [true, false] {
+                    let docs = client.search("idx", "", None, "id", asc, from, size).await.unwrap();
+                    let mut expected = (1..=300).map(|id| id * 3).collect::<Vec<i64>>();
+                    if !asc {
+                        expected.reverse();
+                    }
+                    assert_eq!(sort_ids(&docs), window_model(300, from, size).into_iter().map(|i| expected[i as usize]).collect::<Vec<i64>>(), "{} {} {}", from, size, asc);
+                }
             }
         }
 
-        fn get_string(&self) -> Result<&String, Error> {
-            match self {
-                EsJson::String(s) => Ok(s),
-                _ => Err(Error::Message(String::from("invalid json"))),
+        #[tokio::test]
+        async fn non_integer_sort_values() {
+            // a float at the end of the first batch, read from the sort values of the hits.
+            let docs = numbered(1..=30).into_iter()
+                .map(|(id, source)| if id == "15" { (id, String::from("{\"id\":15.5}")) } else { (id, source) }).collect();
+            let server = Server::with(FakeEs::new(docs).window(100)).await;
+            match small_client(&server).search("idx", "", None, "id", true, 0, 30).await {
+                Err(Error::Message(message)) => assert_eq!(message, "sort field id has non-integer value 15.5."),
+                result => panic!("{:?}", result.map(|docs| docs.len())),
+            }
+
+            // strings, read from the sources of the probes of a deep search.
+            let docs = (0..30).map(|i| (i.to_string(), format!("{{\"id\":\"k{:02}\"}}", i))).collect();
+            let server = Server::with(FakeEs::new(docs).window(100)).await;
+            match small_client(&server).search("idx", "", None, "id", true, 20, 5).await {
+                Err(Error::Message(message)) => assert_eq!(message, "sort field id has non-integer value \"k00\"."),
+                result => panic!("{:?}", result.map(|docs| docs.len())),
             }
         }
 
-        fn find_json(&self, key: &str) -> Result<&EsJson, Error> {
-            let obj = self.get_object()?;
-            match obj.iter().find(|i| i.0 == key) {
-                Some((_, v)) => Ok(v),
-                None => Err(Error::Message(String::from("invalid json"))),
+        #[tokio::test]
+        async fn from_near_i64_max() {
+            let server = Server::with(FakeEs::new(numbered(1..=300)).window(100)).await;
+            let client = small_client(&server);
+            let out_of_range = |result: Result<Vec<String>, Error>| matches!(result, Err(Error::InvalidParameter(message)) if message == "from/size out of range");
+            assert!(out_of_range(client.search("idx", "", None, "id", true, i64::MAX, 1).await));
+            assert!(out_of_range(client.search("idx", "", None, "id", false, i64::MAX - 5, 10).await));
+            assert!(client.explain_plan("idx", "", "id", true, i64::MAX, i64::MAX).await.is_err());
+            assert!(server.requests().is_empty());
+            assert!(client.search("idx", "", None, "id", true, i64::MAX, 0).await.unwrap().is_empty());
+            assert!(client.search("idx", "", None, "id", true, i64::MAX - 5, 5).await.unwrap().is_empty());
+            #[cfg(feature = "parallel")]
+            {
+                let client = server.client().max_from(10).max_size(15).max_result_window(100).concurrency(4).build().unwrap();
+                assert!(out_of_range(client.search("idx", "", None, "id", true, i64::MAX - 20, 40).await));
             }
         }
 
-        fn get_hits(&self) -> Result<&Vec<EsJson>, Error> {
-            let hits = self.find_json("\"hits\"")?.find_json("\"hits\"")?.get_array()?;
-            Ok(hits)
+        #[tokio::test]
+        async fn resume_token_keeps_latest_pit() {
+            let server = Server::with(FakeEs::new(numbered(1..=50)).window(100)).await;
+            let client = small_client(&server);
+            let token = ResumeToken::new("pit-0", "1m", "", "id", true);
+            let (docs, next) = client.search_resume(&token, 30).await.unwrap();
+            assert_eq!(sort_ids(&docs), (1..=30).collect::<Vec<i64>>());
+            let next = next.unwrap();
+            assert_eq!(next.pit, "pit-0++");
+            let pits = server.requests().iter().map(|request| json::parse(&request.body).unwrap().get("pit").unwrap().get("id").unwrap().as_str().unwrap().into_owned()).collect::<Vec<String>>();
+            assert_eq!(pits, vec!["pit-0", "pit-0+"]);
+
+            let next = next.to_string().parse::<ResumeToken>().unwrap();
+            let (docs, last) = client.search_resume(&next, 30).await.unwrap();
+            assert_eq!(sort_ids(&docs), (31..=50).collect::<Vec<i64>>());
+            assert_eq!(last.unwrap().pit, "pit-0+++++");
+            assert!(server.requests()[2].body.contains("\"pit-0++\""));
         }
-    }
 
-    /// Simple json analyzer. 
-    /// In order to keep dependencies low, use this own json analyzer. 
-    struct EsJsonAnalyzer {
-        json: Vec<char>,
-        length: usize,
-        position: usize,
-        character: char,
-    }
+        /// The number of requests of a search of the documents 101 to 105. 
+        async fn deep_search_requests(server: &Server, client: &Client, options: &SearchOptions<'_>) -> usize {
+            let before = server.requests().len();
+            let docs = client.search_with_options("idx", "", None, "id", true, 100, 5, options).await.unwrap();
+            assert_eq!(sort_ids(&docs), (101..=105).collect::<Vec<i64>>());
+            server.requests().len() - before
+        }
 
-    impl EsJsonAnalyzer {
+        #[tokio::test]
+        async fn cached_bounds() {
+            let server = Server::with(FakeEs::new(numbered(1..=300)).window(100)).await;
+            let client = server.client().max_from(10).max_size(15).max_result_window(100).cache_ttl(Duration::from_secs(60)).build().unwrap();
+            let first = deep_search_requests(&server, &client, &SearchOptions::default()).await;
+            // the second search skips the two probes.
+            assert_eq!(deep_search_requests(&server, &client, &SearchOptions::default()).await, first - 2);
+            client.invalidate();
+            assert_eq!(deep_search_requests(&server, &client, &SearchOptions::default()).await, first);
 
-        /// Create a json analyzer.
-        fn new(str: &str) -> EsJsonAnalyzer {
-            let chars = str.chars().collect::<Vec<char>>();
-            let pos = 0;
-            EsJsonAnalyzer {
-                position: pos,
-                character: chars[pos],
-                length: chars.len(),
-                json: chars,
+            // another mapping type or other headers miss the cached bounds of the first search.
+            let options = SearchOptions { doc_type: Some("_doc"), ..Default::default() };
+            assert_eq!(deep_search_requests(&server, &client, &options).await, first);
+            assert_eq!(deep_search_requests(&server, &client, &options).await, first - 2);
+            assert!(server.requests().iter().any(|request| request.path.starts_with("/idx/_doc/_search")));
+            let mut headers = HeaderMap::new();
+            headers.insert("x-tenant", elasticsearch::http::headers::HeaderValue::from_static("a"));
+            let options = SearchOptions { headers: Some(&headers), ..Default::default() };
+            assert_eq!(deep_search_requests(&server, &client, &options).await, first);
+            assert_eq!(deep_search_requests(&server, &client, &options).await, first - 2);
+            assert_eq!(deep_search_requests(&server, &client, &SearchOptions::default()).await, first - 2);
+
+            // a reversed search also counts the documents that have the sort field, the count is cached with the bounds.
+            client.invalidate();
+            let mut sent = vec![];
+            for _ in 0..2 {
+                let before = server.requests().len();
+                let docs = client.search("idx", "", None, "id", true, 250, 5).await.unwrap();
+                assert_eq!(sort_ids(&docs), (251..=255).collect::<Vec<i64>>());
+                sent.push(server.requests()[before..].iter().map(|request| request.body.contains("\"exists\"")).collect::<Vec<bool>>());
             }
+            assert_eq!(sent[0].iter().filter(|exists| **exists).count(), 1);
+            assert_eq!(sent[1].len(), sent[0].len() - 3);
+            assert!(!sent[1].contains(&true));
         }
 
-        /// Goto next json character.
-        fn goto_next_char(&mut self) -> bool {
-            self.position += 1;
-            if self.position < self.length {
-                self.character = self.json[self.position];
-                return true;
+        #[tokio::test]
+        async fn source_resolver_fields() {
+            let server = Server::with(FakeEs::new(numbered(1..=30)).window(100)).await;
+            let client = small_client(&server);
+            let resolver = |batch: usize| if batch == 0 { vec!["id"] } else { vec!["id", "name"] };
+            let options = SearchOptions { source_resolver: Some(&resolver), ..Default::default() };
+            let docs = client.search_with_options("idx", "", None, "id", true, 0, 30, &options).await.unwrap();
+            assert_eq!(sort_ids(&docs), (1..=30).collect::<Vec<i64>>());
+            assert!(docs[0].contains("\"_source\":{\"id\":1}"));
+            assert!(docs[29].contains("\"name\":\"doc 30\""));
+
+            let resolver = |_: usize| vec!["id\"],\"script_fields\":{\"x\":1}}//"];
+            let options = SearchOptions { source_resolver: Some(&resolver), ..Default::default() };
+            let requests = server.requests().len();
+            let result = client.search_with_options("idx", "", None, "id", true, 0, 30, &options).await;
+            assert!(matches!(result, Err(Error::InvalidParameter(_))));
+            assert_eq!(server.requests().len(), requests);
+        }
+
+        #[tokio::test]
+        async fn node_failover() {
+            let server = Server::with(FakeEs::new(numbered(1..=30))).await;
+            let dead = {
+                let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+                format!("http://{}", listener.local_addr().unwrap())
+            };
+            let client = Client::builder().add_node(&dead).add_node(&server.url).add_node(&dead).build().unwrap();
+            for _ in 0..3 {
+                let docs = client.search("idx", "", None, "id", true, 0, 10).await.unwrap();
+                assert_eq!(sort_ids(&docs), (1..=10).collect::<Vec<i64>>());
             }
-            false
+
+            let client = Client::builder().add_node(&dead).add_node(&dead).build().unwrap();
+            assert!(matches!(client.count("idx", "").await, Err(Error::Transport(_))));
+            let client = Client::new(Transport::single_node(&dead).unwrap());
+            assert!(matches!(client.count("idx", "").await, Err(Error::Transport(_))));
         }
 
-        /// Count the number of consecutive occurrences of a specified character before the current character
-        fn count_prev_char(&self, prev_char: char) -> usize {
-            let mut char_count = 0;
-            let mut prev_pos = self.position - 1;
+        #[test]
+        fn auth_with_transport() {
+            let builder = Client::builder().transport(Transport::single_node("http://localhost:9200").unwrap());
+            assert!(matches!(builder.auth_bearer("token").build(), Err(Error::InvalidParameter(_))));
+        }
 
-            loop {
-                let char = self.json[prev_pos];
-                if char == prev_char {
-                    char_count += 1;
-                } else {
-                    break;
-                }
-                prev_pos -= 1;
-                if prev_pos == 0 {
-                    break;
+        #[tokio::test]
+        async fn dense_ties_without_tie_break() {
+            // 2000 documents share the sort value 500, between 100 documents of lower and 100 of higher values.
+            let server = Server::with(FakeEs::new(numbered((1..=2200).map(|i| if (101..=2100).contains(&i) { 500 } else { i }))).window(100)).await;
+            let client = small_client(&server);
+            // the documents of the run ordered by "_id", "1000" before "101".
+            let mut run = (101..=2100).map(|id: i64| id.to_string()).collect::<Vec<String>>();
+            run.sort();
+            for (asc, from) in [(true, 1000), (false, 1000), (true, 150)] {
+                let before = server.requests().len();
+                let docs = client.search("idx", "", None, "id", asc, from, 20).await.unwrap();
+                let ids = docs.iter().map(|doc| json::parse_with_depth(doc, 1000).unwrap().get("_id").unwrap().as_raw_str().unwrap().to_string()).collect::<Vec<String>>();
+                let offset = (from - 100) as usize;
+                assert_eq!(ids, run[offset..offset + 20], "asc {}", asc);
+
+                let requests = &server.requests()[before..];
+                assert!(requests.iter().filter(|request| request.path.ends_with("_search")).all(|request| json::parse(&request.body).unwrap().get("from").and_then(EsJson::as_i64).unwrap_or(0) <= 10));
+                // one point in time to skip the tied documents, one to read the ids of the run.
+                assert_eq!(requests.iter().filter(|request| request.path.starts_with("/idx/_pit")).count(), 2);
+                assert_eq!(requests.iter().filter(|request| request.method == "DELETE").count(), 2);
+            }
+        }
+
+        #[tokio::test]
+        async fn reversed_tie_order() {
+            // runs of 3 documents share each sort value in the last third of 300 documents.
+            let value = |i: i64| if i < 200 { i } else { 200 + (i - 200) / 3 };
+            let server = Server::with(FakeEs::new(numbered((0..300).map(value)))).await;
+            // a single batch, without tie_break a batch boundary within a run skips the rest of the run.
+            let client = server.client().max_from(10).max_size(100).build().unwrap();
+            let ids = |docs: &[String]| docs.iter().map(|doc| json::parse_with_depth(doc, 1000).unwrap().get("_id").unwrap().as_raw_str().unwrap().to_string()).collect::<Vec<String>>();
+            // the order of a query sorted by the sort field and then by "_id".
+            let expected = |asc: bool, from: usize, size: usize| {
+                let mut docs = (0..300).map(|i| (value(i), (i + 1).to_string())).collect::<Vec<(i64, String)>>();
+                docs.sort_by(|a, b| if asc { a.0.cmp(&b.0) } else { b.0.cmp(&a.0) }.then_with(|| a.1.cmp(&b.1)));
+                docs.into_iter().skip(from).take(size).map(|(_, id)| id).collect::<Vec<String>>()
+            };
+
+            let plan = client.explain_plan("idx", "", "id", true, 242, 58).await.unwrap();
+            assert!(plan.reverse);
+            // each run within the window, the window cutting runs at one or both ends, and a window within a single run.
+            for (from, size) in [(242, 58), (243, 57), (201, 10), (204, 1), (0, 50), (40, 70), (0, 300)] {
+                for asc in [true, false] {
+                    let docs = client.search("idx", "", None, "id", asc, from, size).await.unwrap();
+                    assert_eq!(ids(&docs), expected(asc, from as usize, size as usize), "asc {} from {} size {}", asc, from, size);
                 }
             }
-            char_count
         }
 
-        /// Skip space characters.
-        fn skip_space(&mut self) -> i32 {
-            let mut skip_count = 0;
-            const SPACE_CHARS: [char; 4] = [' ', '\t', '\n', '\r'];
-            while SPACE_CHARS.contains(&self.character) {
-                if !self.goto_next_char() {
-                    break;
+        #[tokio::test]
+        async fn partial_on_deadline() {
+            let es = FakeEs::new(numbered(1..=100));
+            let server = Server::start(move |request| {
+                if request.path.contains("_search") {
+                    std::thread::sleep(Duration::from_millis(100));
                 }
-                skip_count += 1;
+                Some(es.handle(request))
+            }).await;
+            let client = server.client().max_size(15).build().unwrap();
+
+            let (docs, stats) = client.search_with_stats("idx", "", None, "id", true, 0, 30, &SearchOptions { partial_on_deadline: true, ..Default::default() }).await.unwrap();
+            assert_eq!(docs.len(), 30);
+            assert!(!stats.truncated());
+
+            let options = SearchOptions { partial_on_deadline: true, deadline: Some(std::time::Instant::now() + Duration::from_millis(250)), ..Default::default() };
+            let (docs, stats) = client.search_with_stats("idx", "", None, "id", true, 0, 100, &options).await.unwrap();
+            assert!(stats.truncated());
+            assert!(!docs.is_empty() && docs.len() < 100, "{}", docs.len());
+            assert_eq!(sort_ids(&docs), (1..=docs.len() as i64).collect::<Vec<i64>>());
+        }
+
+        #[tokio::test]
+        async fn request_timeout() {
+            let server = Server::start(|_| None).await;
+            let client = server.client().timeout(Duration::from_millis(200)).build().unwrap();
+            let start = std::time::Instant::now();
+            assert!(matches!(client.count("idx", "").await, Err(Error::Timeout(timeout)) if timeout == Duration::from_millis(200)));
+            assert!(matches!(client.search("idx", "", None, "id", true, 0, 10).await, Err(Error::Timeout(_))));
+            assert!(start.elapsed() < Duration::from_secs(5));
+        }
+
+        #[tokio::test]
+        async fn timed_out_response() {
+            let es = FakeEs::new(numbered(1..=10));
+            let server = Server::start(move |request| {
+                let (status, body) = es.handle(request);
+                Some((status, body.replace("\"timed_out\":false", "\"timed_out\":true")))
+            }).await;
+            let client = server.client().build().unwrap();
+            match client.search("idx", "", None, "id", true, 0, 10).await {
+                Err(Error::Message(message)) => assert!(message.contains("timed out"), "{}", message),
+                _ => panic!("timed out response accepted"),
             }
-            skip_count
+            let client = server.client().allow_timed_out(true).build().unwrap();
+            let docs = client.search("idx", "", None, "id", true, 0, 10).await.unwrap();
+            assert_eq!(sort_ids(&docs), (1..=10).collect::<Vec<i64>>());
         }
 
-        /// Read a json array.
-        fn read_json_array (&mut self) -> Vec<EsJson> {
-            assert_eq!(self.character, '[');
-            self.goto_next_char();
-            let mut ary = vec![];
-            let mut last_pos : usize = 0;
-            loop {
-                assert_ne!(self.position, last_pos);
-                last_pos = self.position;
+        #[tokio::test]
+        async fn request_headers() {
+            use elasticsearch::http::headers::HeaderValue;
 
-                self.skip_space();
+            let server = Server::with(FakeEs::new(numbered(1..=300)).window(100)).await;
+            let mut defaults = HeaderMap::new();
+            defaults.insert("x-opaque-id", HeaderValue::from_static("client"));
+            defaults.insert("x-tenant", HeaderValue::from_static("t1"));
+            let client = server.client().max_from(10).max_size(15).max_result_window(100).headers(defaults).build().unwrap();
 
-                if self.character == ',' {
-                    self.goto_next_char();
-                    continue;
-                }
-                if self.character == ']' {
-                    self.goto_next_char();
-                    break;
-                }
-                ary.push(self.read_json_value());
+            client.search("idx", "", None, "id", true, 100, 20).await.unwrap();
+            let requests = server.requests();
+            assert!(requests.len() > 2);
+            for request in &requests {
+                assert_eq!(request.header("x-opaque-id"), ["client"], "{}", request.path);
+                assert_eq!(request.header("x-tenant"), ["t1"], "{}", request.path);
+            }
+
+            let mut headers = HeaderMap::new();
+            headers.insert("x-opaque-id", HeaderValue::from_static("call"));
+            let options = SearchOptions { headers: Some(&headers), ..Default::default() };
+            client.search_with_options("idx", "", None, "id", true, 100, 20, &options).await.unwrap();
+            for request in &server.requests()[requests.len()..] {
+                assert_eq!(request.header("x-opaque-id"), ["call"], "{}", request.path);
+                assert_eq!(request.header("x-tenant"), ["t1"], "{}", request.path);
             }
-            ary
         }
 
-        /// Read a json value.
-        fn read_json_value(&mut self) -> EsJson {
-            match self.character {
-                '\"' => EsJson::String(self.read_json_string()),
-                '{' => EsJson::Object(self.read_json_object()),
-                '[' => EsJson::Array(self.read_json_array()),
-                _ => EsJson::String(self.read_json_literal())
+        #[tokio::test]
+        async fn nested_sort_field() {
+            // the sort values are nested two levels deep in the source.
+            let docs = (1..=300).map(|i| (i.to_string(), format!("{{\"id\":{},\"meta\":{{\"seq\":{}}}}}", i, i * 3))).collect();
+            let server = Server::with(FakeEs::new(docs).window(100)).await;
+            let client = small_client(&server);
+            let docs = client.search("idx", "", None, "meta.seq", true, 100, 20).await.unwrap();
+            assert_eq!(sort_ids(&docs), (101..=120).collect::<Vec<i64>>());
+            let docs = client.search("idx", "", None, "meta.seq", false, 250, 20).await.unwrap();
+            assert_eq!(sort_ids(&docs), (31..=50).rev().collect::<Vec<i64>>());
+            assert!(server.requests().iter().any(|request| request.body.contains("\"_source\": [\"meta.seq\"]")));
+        }
+
+        #[tokio::test]
+        async fn dedup_colliding_sort_values() {
+            // every four documents share a sort value, so the batch boundaries of 15 documents fall inside the groups.
+            let docs = (1..=200).map(|i| (i.to_string(), format!("{{\"id\":{},\"group\":{}}}", i, (i - 1) / 4))).collect();
+            let server = Server::with(FakeEs::new(docs).window(100)).await;
+            let client = small_client(&server);
+            let skipped = client.search("idx", "", None, "group", true, 0, 200).await.unwrap();
+            assert!(skipped.len() < 200);
+            let options = SearchOptions { dedup: true, ..Default::default() };
+            for asc in [true, false] {
+                let docs = client.search_with_options("idx", "", None, "group", asc, 0, 200, &options).await.unwrap();
+                let mut ids = sort_ids(&docs);
+                ids.sort();
+                assert_eq!(ids, (1..=200).collect::<Vec<i64>>(), "asc {}", asc);
             }
+            assert!(server.requests().iter().any(|request| request.body.contains("\"must_not\":{\"ids\":{\"values\":[")));
         }
 
-        /// Read a json object.
-        fn read_json_object (&mut self) -> Vec<(String, EsJson)> {
-            assert_eq!(self.character, '{');
-            self.goto_next_char();
-            let mut obj = vec![];
-            let mut last_pos = 0;
-            loop {
-                assert_ne!(self.position, last_pos);
+        #[tokio::test]
+        async fn collapse_clause() {
+            let server = Server::with(FakeEs::new(numbered(1..=50)).window(100)).await;
+            let client = small_client(&server);
+            let options = SearchOptions { collapse: Some("user_id"), ..Default::default() };
+            let docs = client.search_with_options("idx", "", None, "id", true, 5, 10, &options).await.unwrap();
+            assert_eq!(sort_ids(&docs), (6..=15).collect::<Vec<i64>>());
+            let requests = server.requests();
+            assert_eq!(requests.len(), 1);
+            assert!(requests[0].body.contains("\"collapse\":{\"field\":\"user_id\"}"), "{}", requests[0].body);
+            assert!(json::parse(&requests[0].body).is_ok());
+            assert!(matches!(client.search_with_options("idx", "", None, "id", true, 20, 10, &options).await, Err(Error::InvalidParameter(_))));
+            assert!(matches!(client.search_with_options("idx", "", None, "id", true, 0, 20, &options).await, Err(Error::InvalidParameter(_))));
+            assert_eq!(server.requests().len(), 1);
+        }
 
-                last_pos = self.position;
-                self.skip_space();
-                if self.character == ',' {
-                    self.goto_next_char();
-                    continue;
-                }
-                if self.character == '}' {
-                    self.goto_next_char();
-                    break;
-                }
-                let key_value = self.read_json_key_value();
-                obj.push(key_value);
+        #[tokio::test]
+        async fn sort_values_near_integer_limits() {
+            let docs = |first: u64| (1..=300u64).map(|i| (i.to_string(), format!("{{\"id\":{},\"sid\":{}}}", i, first + i))).collect();
+            let server = Server::with(FakeEs::new(docs(i64::MAX as u64 - 300)).window(100)).await;
+            let client = small_client(&server);
+            assert_eq!(sort_ids(&client.search("idx", "", None, "sid", true, 100, 20).await.unwrap()), (101..=120).collect::<Vec<i64>>());
+            assert_eq!(sort_ids(&client.search("idx", "", None, "sid", false, 250, 20).await.unwrap()), (31..=50).rev().collect::<Vec<i64>>());
+
+            // the values straddle i64::MAX and end at u64::MAX.
+            for first in [i64::MAX as u64 - 150, u64::MAX - 300] {
+                let server = Server::with(FakeEs::new(docs(first)).window(100)).await;
+                let client = small_client(&server);
+                assert_eq!(sort_ids(&client.search_u64("idx", "", None, "sid", true, 100, 20).await.unwrap()), (101..=120).collect::<Vec<i64>>());
+                assert_eq!(sort_ids(&client.search_u64("idx", "", None, "sid", true, 280, 50).await.unwrap()), (281..=300).collect::<Vec<i64>>());
+                assert_eq!(sort_ids(&client.search_u64("idx", "", None, "sid", false, 250, 20).await.unwrap()), (31..=50).rev().collect::<Vec<i64>>());
             }
-            obj
         }
 
-        /// Read a json literal.
-        fn read_json_literal(&mut self) -> String {
-            const STOP_CHARS: [char; 7] = [' ', '\t', '\r', '\n', ',', ']', '}'];
-            let mut literal = String::new();
-            loop {
-                if STOP_CHARS.contains(&self.character) {
-                    break;
-                }
-                literal.push(self.character);
-                if !self.goto_next_char() {
-                    break;
-                }
+        #[tokio::test]
+        async fn server_error_retry() {
+            use std::sync::atomic::{AtomicUsize, Ordering};
+
+            // the first `failures` requests fail with the statuses in turn, the following ones are answered by the fake index.
+            let flaky = |statuses: Vec<u16>, failures: usize| {
+                let es = FakeEs::new(numbered(1..=10));
+                let sent = AtomicUsize::new(0);
+                Server::start(move |request| match sent.fetch_add(1, Ordering::SeqCst) {
+                    n if n < failures => Some((statuses[n % statuses.len()], String::from("{\"error\":\"unavailable\",\"status\":503}"))),
+                    _ => Some(es.handle(request)),
+                })
+            };
+            let retry = Retry { max_retries: 3, initial_backoff: Duration::from_millis(1), max_backoff: Duration::from_millis(5), retry_unavailable: false };
+
+            let server = flaky(vec![502, 503, 504], 3).await;
+            let client = server.client().server_error_retry(retry).build().unwrap();
+            assert_eq!(client.count("idx", "").await.unwrap(), 10);
+            assert_eq!(server.requests().len(), 4);
+
+            let server = flaky(vec![503, 504], usize::MAX).await;
+            let client = server.client().server_error_retry(retry).build().unwrap();
+            assert!(matches!(client.count("idx", "").await, Err(Error::Http { status: 504, .. })));
+            assert_eq!(server.requests().len(), 4);
+
+            for status in [500, 400] {
+                let server = flaky(vec![status], usize::MAX).await;
+                let client = server.client().server_error_retry(retry).build().unwrap();
+                assert!(matches!(client.count("idx", "").await, Err(Error::Http { status: s, .. }) if s == status));
+                assert_eq!(server.requests().len(), 1);
             }
-            literal
         }
 
-        /// Read a json string.
-        fn read_json_string(&mut self) -> String {
-            assert_eq!(self.character, '\"');
-            let mut string = String::from(self.character);
-            while self.goto_next_char() {
-                string.push(self.character);
-                if self.character == '\"' && self.count_prev_char('\\') % 2 == 0 {
-                    self.goto_next_char();
-                    break;
+        #[tokio::test]
+        async fn first_batch_within_window() {
+            // from is within max_from, but from plus the batch size exceeds the window.
+            let server = Server::with(FakeEs::new(numbered(1..=300)).window(100)).await;
+            let client = server.client().max_from(50).max_size(80).max_result_window(100).build().unwrap();
+            for asc in [true, false] {
+                let docs = client.search("idx", "", None, "id", asc, 40, 200).await.unwrap();
+                let expected = match asc {
+                    true => (41..=240).collect::<Vec<i64>>(),
+                    false => (61..=260).rev().collect::<Vec<i64>>(),
+                };
+                assert_eq!(sort_ids(&docs), expected);
+            }
+            for request in server.requests().iter().filter(|request| request.path.ends_with("/_search")) {
+                let body = json::parse(&request.body).unwrap();
+                let from = body.get("from").and_then(EsJson::as_i64).unwrap_or(0);
+                assert!(from + body.get("size").and_then(EsJson::as_i64).unwrap_or(10) <= 100, "{}", request.body);
+            }
+        }
+
+        #[tokio::test]
+        async fn probe_errors() {
+            // the probe requests of the failing directions fail with 500, the other requests are answered by the fake index.
+            let failing = |orders: &'static [&'static str]| {
+                let es = FakeEs::new(numbered(1..=300)).window(100);
+                Server::start(move |request| {
+                    let body = match request.body.is_empty() {
+                        true => return Some(es.handle(request)),
+                        false => json::parse(&request.body).unwrap(),
+                    };
+                    let order = body.get("sort").and_then(|sort| sort.get("id")).and_then(EsJson::as_raw_str).unwrap_or_default();
+                    match body.get("size").and_then(EsJson::as_i64) == Some(1) && orders.contains(&order) {
+                        true => Some((500, format!("{{\"error\":\"{} probe failed\",\"status\":500}}", order))),
+                        false => Some(es.handle(request)),
+                    }
+                })
+            };
+            for (orders, phase) in [(&["asc"][..], "probe-min: "), (&["desc"][..], "probe-max: ")] {
+                let server = failing(orders).await;
+                let client = small_client(&server);
+                match client.search("idx", "", None, "id", true, 100, 20).await {
+                    Err(Error::Http { status: 500, body }) => assert!(body.starts_with(phase) && body.contains(orders[0]), "{}", body),
+                    result => panic!("{:?}", result.map(|docs| docs.len())),
                 }
             }
-            string
+            let server = failing(&["asc", "desc"]).await;
+            let client = small_client(&server);
+            assert!(matches!(client.search("idx", "", None, "id", true, 100, 20).await, Err(Error::Http { status: 500, .. })));
+            let server = failing(&[]).await;
+            let client = small_client(&server);
+            assert_eq!(sort_ids(&client.search("idx", "", None, "id", true, 100, 20).await.unwrap()), (101..=120).collect::<Vec<i64>>());
         }
 
-        /// Read a json key-value pair.
-        fn read_json_key_value(&mut self) -> (String, EsJson) {
-            self.skip_space();
-            assert_eq!(self.character, '\"');
-            let key = self.read_json_string();
-            self.skip_space();
-            assert_eq!(self.character, ':');
-            self.goto_next_char();
-            self.skip_space();
-            (key, self.read_json_value())
+        #[tokio::test]
+        async fn embedded_sort() {
+            let embedded = |result: Result<&str, Error>| matches!(result, Err(Error::InvalidParameter(message)) if message.contains("use the sort parameter"));
+            assert!(embedded(Client::normalize_query("{\"query\":{\"match_all\":{}},\"sort\":[{\"id\":\"desc\"}]}")));
+            assert!(embedded(Client::normalize_query("{\"sort\":{\"id\":\"asc\"},\"term\":{\"tag\":\"a\"}}")));
+            assert!(matches!(Client::normalize_query("{\"term\":"), Err(Error::InvalidParameter(_))));
+            assert_eq!(Client::normalize_query("").unwrap(), "{\"match_all\":{}}");
+            for query in ["{\"term\":{\"sort\":\"a\"}}", "{\"bool\":{\"must\":[{\"term\":{\"tag\":\"sort\"}}]}}"] {
+                assert_eq!(Client::normalize_query(query).unwrap(), query);
+            }
+
+            let server = Server::with(FakeEs::new(numbered(1..=10))).await;
+            let client = server.client().build().unwrap();
+            assert!(embedded(client.search("idx", "{\"match_all\":{},\"sort\":[\"id\"]}", None, "id", true, 0, 10).await.map(|_| "")));
+            assert!(server.requests().is_empty());
         }
 
-        /// Deserialize json string to EsJson.
-        fn from_json(json: &str) -> EsJson {
-            EsJsonAnalyzer::new(json).read_json_value()
+        #[tokio::test]
+        async fn count_empty_query() {
+            let docs = (1..=30).map(|i| (i.to_string(), format!("{{\"id\":{},\"tag\":\"{}\"}}", i, if i % 3 == 0 { "a" } else { "b" }))).collect();
+            let server = Server::with(FakeEs::new(docs)).await;
+            let client = server.client().build().unwrap();
+            assert_eq!(client.count("idx", "").await.unwrap(), 30);
+            assert_eq!(client.count("idx", "{\"term\":{\"tag\":\"a\"}}").await.unwrap(), 10);
+            let requests = server.requests();
+            assert!(requests[0].path.ends_with("/_count"));
+            assert_eq!(json::parse(&requests[0].body).unwrap().get("query").map(EsJsonAnalyzer::to_json).as_deref(), Some("{\"match_all\":{}}"));
         }
 
-        /// Serialize EsJson to json string.
-        fn to_json(obj: &EsJson) -> String {
-            match obj {
-                EsJson::Array(ary) => {
-                    let ary_str = ary.iter().map(|v| Self::to_json(v)).collect::<Vec<String>>().join(",");
-                    return format!("[{}]", ary_str);
-                }
-                EsJson::Object(obj) => {
-                    let obj_str = obj.iter().map(|(k, v)| format!("{}:{}", k, Self::to_json(v))).collect::<Vec<String>>().join(",");
-                    return format!("{{{}}}", obj_str);
-                }
-                EsJson::String(str) => {
-                    return format!("{}", str);
-                }
+        #[tokio::test]
+        async fn exists_and_template() {
+            let server = Server::with(FakeEs::new(numbered(1..=10))).await;
+            let client = server.client().build().unwrap();
+            assert!(client.exists("idx", "").await.unwrap());
+            assert!(!client.exists("idx", "{\"term\":{\"id\":11}}").await.unwrap());
+            let body = json::parse(&server.requests()[0].body).unwrap();
+            assert_eq!(body.get("terminate_after").and_then(EsJson::as_i64), Some(1));
+
+            // without a total in the response, the hits tell whether a document matches.
+            let responses = ["{\"timed_out\":false,\"hits\":{\"hits\":[]}}", "{\"timed_out\":false,\"hits\":{\"hits\":[{\"_id\":\"1\"}]}}"];
+            for (response, exists) in responses.into_iter().zip([false, true]) {
+                let server = Server::start(move |_| Some((200, String::from(response)))).await;
+                assert_eq!(server.client().build().unwrap().exists("idx", "").await.unwrap(), exists);
+            }
+
+            let template = "{\"timed_out\":true,\"hits\":{\"total\":{\"value\":1,\"relation\":\"eq\"},\"hits\":[{\"_id\":\"1\",\"_source\":{\"id\":1}}]}}";
+            let server = Server::start(move |_| Some((200, String::from(template)))).await;
+            for allow_timed_out in [false, true] {
+                let client = server.client().allow_timed_out(allow_timed_out).build().unwrap();
+                assert_eq!(client.exists("idx", "").await.is_ok(), allow_timed_out);
+                assert_eq!(client.search_template("idx", "tpl", "").await.is_ok(), allow_timed_out);
+            }
+            assert!(server.requests().iter().any(|request| request.path == "/idx/_search/template"));
+
+            let client = server.client().allow_timed_out(true).build().unwrap();
+            client.search_template("idx", "tpl \"a\\b\"", "{\"size\":1}").await.unwrap();
+            let body = json::parse(&server.requests().last().unwrap().body).unwrap();
+            assert_eq!(body.get("id").and_then(EsJson::as_str).as_deref(), Some("tpl \"a\\b\""));
+        }
+
+        #[tokio::test]
+        async fn scroll_parameters() {
+            let server = Server::start(|request| Some(match request.path.as_str() {
+                "/idx/_search?scroll=1m" => (200, String::from("{\"_scroll_id\":\"a\\\"b\\\\c\",\"hits\":{\"hits\":[{\"_id\":\"1\"}]}}")),
+                _ if request.method == "DELETE" => (200, String::from("{\"succeeded\":true}")),
+                _ => (200, String::from("{\"_scroll_id\":\"a\\\"b\\\\c\",\"hits\":{\"hits\":[]}}")),
+            })).await;
+            let client = server.client().build().unwrap();
+            assert_eq!(client.scroll("idx", "", None, "1m", 10).await.unwrap().len(), 1);
+            let requests = server.requests();
+            assert_eq!(requests.len(), 3);
+            for request in &requests[1..] {
+                let body = json::parse(&request.body).unwrap();
+                let scroll_id = match body.get("scroll_id") {
+                    Some(EsJson::Array(ids)) => ids[0].as_str(),
+                    scroll_id => scroll_id.and_then(EsJson::as_str),
+                };
+                assert_eq!(scroll_id.as_deref(), Some("a\"b\\c"), "{}", request.body);
+            }
+
+            for scroll_timeout in ["1m&pretty", "1\"m", "m", "1 m", "10y"] {
+                assert!(matches!(client.scroll("idx", "", None, scroll_timeout, 10).await, Err(Error::InvalidParameter(_))), "{}", scroll_timeout);
+            }
+            assert_eq!(server.requests().len(), 3);
+        }
+
+        #[tokio::test]
+        async fn pit_parameters() {
+            let server = Server::start(|request| Some(match request.method.as_str() {
+                "POST" => (200, String::from("{\"id\":\"p\\\"1\"}")),
+                _ => (200, String::from("{\"succeeded\":true,\"num_freed\":1}")),
+            })).await;
+            let client = server.client().build().unwrap();
+            let pit = client.open_pit("idx", "5m").await.unwrap();
+            assert_eq!(pit, "p\"1");
+            client.close_pit(&pit).await.unwrap();
+            let requests = server.requests();
+            assert_eq!(requests[0].path, "/idx/_pit?keep_alive=5m");
+            assert_eq!(json::parse(&requests[1].body).unwrap().get("id").and_then(EsJson::as_str).as_deref(), Some("p\"1"));
+            let body = json::parse(&format!("{{{}}}", Client::build_pit(&pit, Some("1m")))).unwrap();
+            assert_eq!(body.get("pit").and_then(|pit| pit.get("id")).and_then(EsJson::as_str).as_deref(), Some("p\"1"));
+
+            for keep_alive in ["5m&pretty", "5\"m", "", "5"] {
+                assert!(matches!(client.open_pit("idx", keep_alive).await, Err(Error::InvalidParameter(_))), "{}", keep_alive);
             }
+            assert_eq!(server.requests().len(), 2);
+        }
+
+        #[tokio::test]
+        async fn max_documents_limit() {
+            let server = Server::with(FakeEs::new(numbered(1..=30))).await;
+            let client = server.client().max_documents(20).build().unwrap();
+            let too_many = matches!(client.search("idx", "", None, "id", true, 0, 21).await, Err(Error::InvalidParameter(message)) if message.contains("max_documents"));
+            assert!(too_many);
+            assert!(server.requests().is_empty());
+            assert_eq!(sort_ids(&client.search("idx", "", None, "id", true, 5, 20).await.unwrap()), (6..=25).collect::<Vec<i64>>());
+        }
+
+        #[cfg(feature = "serde")]
+        #[tokio::test]
+        async fn search_as_sources() {
+            #[derive(serde::Deserialize, Debug, PartialEq)]
+            enum Kind { Plain, Tagged(String), Range { low: i64, high: u64 } }
+            #[derive(serde::Deserialize, Debug, PartialEq)]
+            struct Doc { id: i64, name: String, score: f64, note: Option<String>, tags: Vec<String>, kind: Kind, nested: std::collections::HashMap<String, bool> }
+
+            let docs = vec![
+                ("1".to_string(), "{\"id\":1,\"name\":\"a \\\"quoted\\\" \\u00e9\",\"score\":1.5,\"note\":null,\"tags\":[],\"kind\":\"Plain\",\"nested\":{\"x\\ty\":true}}".to_string()),
+                ("2".to_string(), "{\"id\":2,\"name\":\"b\",\"score\":-2,\"note\":\"n\",\"tags\":[\"t1\",\"t2\"],\"kind\":{\"Tagged\":\"t\"},\"nested\":{}}".to_string()),
+                ("3".to_string(), "{\"id\":3,\"name\":\"c\",\"score\":0,\"tags\":[\"t\"],\"kind\":{\"Range\":{\"low\":-1,\"high\":18446744073709551615}},\"nested\":{\"z\":false},\"extra\":[1]}".to_string()),
+            ];
+            let server = Server::with(FakeEs::new(docs)).await;
+            let client = server.client().build().unwrap();
+            let docs = client.search_as::<Doc>("idx", "", None, "id", true, 0, 10).await.unwrap();
+            assert_eq!(docs, vec![
+                Doc { id: 1, name: "a \"quoted\" \u{e9}".to_string(), score: 1.5, note: None, tags: vec![], kind: Kind::Plain, nested: [("x\ty".to_string(), true)].into() },
+                Doc { id: 2, name: "b".to_string(), score: -2.0, note: Some("n".to_string()), tags: vec!["t1".to_string(), "t2".to_string()], kind: Kind::Tagged("t".to_string()), nested: [].into() },
+                Doc { id: 3, name: "c".to_string(), score: 0.0, note: None, tags: vec!["t".to_string()], kind: Kind::Range { low: -1, high: u64::MAX }, nested: [("z".to_string(), false)].into() },
+            ]);
+
+            #[derive(serde::Deserialize, Debug)]
+            #[allow(dead_code)]
+            struct Wrong { id: String }
+            assert!(matches!(client.search_as::<Wrong>("idx", "", None, "id", true, 0, 10).await, Err(Error::Parse { .. })));
         }
     }
 }